@@ -0,0 +1,384 @@
+use std::{sync::Arc, time::Duration};
+
+use crate::connectors::prelude::*;
+use crate::{manager::AlchemyManager, network::AlchemyNetwork};
+
+/// Handshake headers managed by the websocket handshake itself
+const RESERVED_HEADERS: [&str; 4] = ["host", "upgrade", "connection", "origin"];
+
+/// A builder for an [AlchemyManager](AlchemyManager)
+///
+/// The manager is configured either from an api key and [AlchemyNetwork](AlchemyNetwork), or from a raw url.
+/// Every setting is validated by [build](AlchemyManagerBuilder::build).
+///
+/// ## Example
+///
+/// ```rust
+/// use std::time::Duration;
+///
+/// use alchemy_rs::prelude::*;
+///
+/// let manager = AlchemyManager::builder()
+///     .with_api_key("MVNYMOb_58bAMzhXX2pS25NDiZ3Q9HeC")
+///     .with_network(AlchemyNetwork::EthMainnet)
+///     .with_request_timeout(Duration::from_secs(10))
+///     .build()
+///     .expect("Invalid manager configuration");
+/// assert_eq!(manager.url, "wss://eth-mainnet.g.alchemy.com/v2/MVNYMOb_58bAMzhXX2pS25NDiZ3Q9HeC");
+/// ```
+#[derive(Debug, Clone)]
+pub struct AlchemyManagerBuilder {
+    /// The alchemy api key
+    pub api_key: Option<String>,
+    /// The alchemy network, defaulting to ethereum mainnet with an api key
+    pub network: Option<AlchemyNetwork>,
+    /// A raw websocket url, in place of an api key and network, or the socket path of an ipc connector
+    pub url: Option<String>,
+    /// The type of connector
+    pub connector_type: AlchemyConnectorType,
+    /// The time allowed to establish a connection, unlimited if `None`
+    pub connect_timeout: Option<Duration>,
+    /// The time to wait for the response to a json-rpc request
+    pub request_timeout: Duration,
+    /// Additional headers sent with the handshake request of raw connections
+    pub headers: Vec<(String, String)>,
+    /// The keepalive configuration of raw connections, disabling pings if `None`
+    pub keepalive: Option<KeepaliveConfig>,
+    /// The policy used to reconnect after the connection drops
+    pub reconnect_policy: ReconnectPolicy,
+    /// The maximum size of an inbound message on raw connections, using soketto's default if `None`
+    pub max_message_size: Option<usize>,
+    /// Whether to verify the chain id of the connection against the network on connect
+    pub verify_chain_id: bool,
+    /// The transport opening the stream of raw connections, tcp if `None`
+    pub transport: Option<Arc<dyn Transport>>,
+    /// The interval the filters emulating subscriptions over http are polled at
+    pub poll_interval: Duration,
+    /// The number of notifications buffered for each subscription until they are received
+    pub notification_buffer_size: usize,
+    /// The number of connections of a pool connector
+    pub pool_size: usize,
+    /// The maximum number of subscriptions placed on one connection of a pool connector, unlimited if `None`
+    pub max_subscriptions_per_connection: Option<usize>,
+    /// The urls of the backup endpoints of a failover connector, in order of preference
+    pub backup_urls: Vec<String>,
+    /// The health probing of a failover connector
+    pub failover: FailoverConfig,
+}
+
+impl Default for AlchemyManagerBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlchemyManagerBuilder {
+    /// Create a new builder with default settings
+    pub fn new() -> Self {
+        Self {
+            api_key: None,
+            network: None,
+            url: None,
+            connector_type: AlchemyConnectorType::default(),
+            connect_timeout: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            headers: vec![],
+            keepalive: Some(KeepaliveConfig::default()),
+            reconnect_policy: ReconnectPolicy::default(),
+            max_message_size: None,
+            verify_chain_id: false,
+            transport: None,
+            poll_interval: DEFAULT_FILTER_POLL_INTERVAL,
+            notification_buffer_size: DEFAULT_NOTIFICATION_BUFFER_SIZE,
+            pool_size: DEFAULT_POOL_SIZE,
+            max_subscriptions_per_connection: None,
+            backup_urls: vec![],
+            failover: FailoverConfig::default(),
+        }
+    }
+
+    /// Set the alchemy api key
+    pub fn with_api_key(mut self, api_key: &str) -> Self {
+        self.api_key = Some(api_key.to_string());
+        self
+    }
+
+    /// Set the alchemy network, defaulting to ethereum mainnet with an api key
+    ///
+    /// With a raw url, the network describes the chain the url serves.
+    pub fn with_network(mut self, network: AlchemyNetwork) -> Self {
+        self.network = Some(network);
+        self
+    }
+
+    /// Connect to a raw websocket url, in place of an api key and network
+    ///
+    /// The [Http](AlchemyConnectorType::Http) connector takes an `http://` or `https://` url,
+    /// and the [Ipc](AlchemyConnectorType::Ipc) connector the path of its unix domain socket instead.
+    pub fn with_url(mut self, url: &str) -> Self {
+        self.url = Some(url.to_string());
+        self
+    }
+
+    /// Set the type of connector
+    pub fn with_connector_type(mut self, connector_type: AlchemyConnectorType) -> Self {
+        self.connector_type = connector_type;
+        self
+    }
+
+    /// Set the time allowed to establish a connection
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the time to wait for the response to a json-rpc request
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Add a header to send with the handshake request of raw connections
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Set the keepalive configuration of raw connections, disabling pings if `None`
+    pub fn with_keepalive(mut self, keepalive: Option<KeepaliveConfig>) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    /// Set the [ReconnectPolicy](ReconnectPolicy) used when the connection drops
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    /// Set the maximum size of an inbound message on raw connections
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = Some(max_message_size);
+        self
+    }
+
+    /// Verify the chain id of the connection against the network on connect
+    pub fn with_chain_id_verification(mut self, verify: bool) -> Self {
+        self.verify_chain_id = verify;
+        self
+    }
+
+    /// Set the [Transport](Transport) opening the stream of raw connections, eg a unix socket or an in-memory pipe
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Set the interval the filters emulating subscriptions over http are polled at
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Set the number of notifications buffered for each subscription until they are received
+    ///
+    /// Notifications arriving while a subscription's buffer is full are dropped.
+    pub fn with_notification_buffer_size(mut self, size: usize) -> Self {
+        self.notification_buffer_size = size;
+        self
+    }
+
+    /// Set the number of connections of a [Pool](AlchemyConnectorType::Pool) connector
+    pub fn with_pool_size(mut self, size: usize) -> Self {
+        self.pool_size = size;
+        self
+    }
+
+    /// Set the maximum number of subscriptions placed on one connection of a [Pool](AlchemyConnectorType::Pool) connector
+    pub fn with_max_subscriptions_per_connection(mut self, max: usize) -> Self {
+        self.max_subscriptions_per_connection = Some(max);
+        self
+    }
+
+    /// Add a backup websocket url to fail over to with a [Failover](AlchemyConnectorType::Failover) connector
+    pub fn with_backup_url(mut self, url: &str) -> Self {
+        self.backup_urls.push(url.to_string());
+        self
+    }
+
+    /// Set the [FailoverConfig](FailoverConfig) probing the endpoints of a [Failover](AlchemyConnectorType::Failover) connector
+    pub fn with_failover_config(mut self, config: FailoverConfig) -> Self {
+        self.failover = config;
+        self
+    }
+
+    /// The websocket (or http) url of the configured api key and network, or the raw url
+    pub fn endpoint(&self) -> Result<String, AlchemyConnectionError> {
+        if self.connector_type == AlchemyConnectorType::Ipc {
+            return match (&self.url, &self.api_key) {
+                (Some(path), None) if !path.is_empty() => Ok(path.clone()),
+                _ => Err(invalid("the ipc connector requires a socket path as url")),
+            };
+        }
+        match (&self.url, &self.api_key) {
+            (Some(_), Some(_)) => Err(invalid("both a url and an api key are set")),
+            (None, None) => Err(invalid("either a url or an api key is required")),
+            (Some(url), None) => {
+                if self.connector_type == AlchemyConnectorType::Http {
+                    parse_http_url(url)?;
+                } else {
+                    AlchemyUrl::parse(url)?;
+                }
+                Ok(url.clone())
+            }
+            (None, Some(api_key)) => {
+                if !is_slug(api_key, |c| {
+                    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+                }) {
+                    return Err(invalid("the api key must be alphanumeric"));
+                }
+                let network = self.network.unwrap_or_default();
+                match self.connector_type {
+                    AlchemyConnectorType::Http => Ok(network.http_url(api_key)),
+                    _ => Ok(network.ws_url(api_key)),
+                }
+            }
+        }
+    }
+
+    /// Validate the configuration and build the [AlchemyManager](AlchemyManager)
+    pub fn build(self) -> Result<AlchemyManager, AlchemyConnectionError> {
+        let url = self.endpoint()?;
+        let network = match self.api_key {
+            Some(_) => Some(self.network.unwrap_or_default()),
+            None => self.network,
+        };
+
+        if self.connect_timeout == Some(Duration::ZERO) {
+            return Err(invalid("the connect timeout must not be zero"));
+        }
+        if self.request_timeout.is_zero() {
+            return Err(invalid("the request timeout must not be zero"));
+        }
+        if self.poll_interval.is_zero() {
+            return Err(invalid("the poll interval must not be zero"));
+        }
+        if self.notification_buffer_size == 0 {
+            return Err(invalid("the notification buffer size must not be zero"));
+        }
+        if self.pool_size == 0 || self.max_subscriptions_per_connection == Some(0) {
+            return Err(invalid(
+                "the pool size and subscriptions per connection must not be zero",
+            ));
+        }
+        if self.connector_type == AlchemyConnectorType::Failover {
+            if self.backup_urls.is_empty() {
+                return Err(invalid("the failover connector requires a backup url"));
+            }
+            for backup in &self.backup_urls {
+                AlchemyUrl::parse(backup)?;
+            }
+            let failover = &self.failover;
+            if failover.probe_interval.is_zero()
+                || failover.probe_timeout.is_zero()
+                || failover.max_failures == 0
+            {
+                return Err(invalid(
+                    "the failover probe interval, timeout and failures must not be zero",
+                ));
+            }
+        } else if !self.backup_urls.is_empty() {
+            return Err(invalid(
+                "backup urls are only supported by the failover connector",
+            ));
+        }
+        if self.max_message_size == Some(0) {
+            return Err(invalid("the maximum message size must not be zero"));
+        }
+        if let Some(keepalive) = &self.keepalive {
+            if keepalive.ping_interval.is_zero()
+                || keepalive.max_missed_pongs == 0
+                || keepalive.write_timeout.is_zero()
+            {
+                return Err(invalid(
+                    "the keepalive ping interval, missed pongs and write timeout must not be zero",
+                ));
+            }
+        }
+        let policy = &self.reconnect_policy;
+        if policy.multiplier < 1.0 || !(0.0..=1.0).contains(&policy.jitter) {
+            return Err(invalid(
+                "the reconnect multiplier must be at least 1 and the jitter within 0 and 1",
+            ));
+        }
+        for (name, _) in &self.headers {
+            let lowercase = name.to_ascii_lowercase();
+            if !is_slug(name, |c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                return Err(invalid(&format!("invalid header name: {:?}", name)));
+            }
+            if RESERVED_HEADERS.contains(&lowercase.as_str())
+                || lowercase.starts_with("sec-websocket-")
+            {
+                return Err(invalid(&format!("reserved header: {:?}", name)));
+            }
+        }
+        if self.verify_chain_id && network.is_none() {
+            return Err(invalid("verifying the chain id requires a network"));
+        }
+        if !self.headers.is_empty()
+            && !matches!(
+                self.connector_type,
+                AlchemyConnectorType::Raw
+                    | AlchemyConnectorType::Http
+                    | AlchemyConnectorType::Pool
+                    | AlchemyConnectorType::Failover
+            )
+        {
+            return Err(invalid(
+                "headers are only supported by the raw, pool, failover and http connectors",
+            ));
+        }
+        if self.transport.is_some()
+            && !matches!(
+                self.connector_type,
+                AlchemyConnectorType::Raw
+                    | AlchemyConnectorType::Pool
+                    | AlchemyConnectorType::Failover
+            )
+        {
+            return Err(invalid(
+                "transports are only supported by the raw, pool and failover connectors",
+            ));
+        }
+
+        let mut manager = AlchemyManager::new(&url, Some(self.connector_type))
+            .with_reconnect_policy(self.reconnect_policy)
+            .with_keepalive(self.keepalive)
+            .with_request_timeout(self.request_timeout)
+            .with_poll_interval(self.poll_interval)
+            .with_notification_buffer_size(self.notification_buffer_size)
+            .with_pool_size(self.pool_size)
+            .with_max_subscriptions_per_connection(self.max_subscriptions_per_connection)
+            .with_failover_config(self.failover)
+            .with_chain_id_verification(self.verify_chain_id);
+        manager.network = network;
+        manager.connect_timeout = self.connect_timeout;
+        manager.headers = self.headers;
+        manager.max_message_size = self.max_message_size;
+        manager.backup_urls = self.backup_urls;
+        if let Some(transport) = self.transport {
+            manager.transport = transport;
+        }
+        Ok(manager)
+    }
+}
+
+/// Whether the string is non-empty and only made of allowed characters
+fn is_slug(s: &str, allowed: impl Fn(char) -> bool) -> bool {
+    !s.is_empty() && s.chars().all(allowed)
+}
+
+/// An invalid configuration error
+fn invalid(reason: &str) -> AlchemyConnectionError {
+    AlchemyConnectionError::InvalidConfig(reason.to_string())
+}
@@ -0,0 +1,409 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ethers::types::{Address, Block, Filter, Log, Transaction, TxHash};
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::mpsc;
+
+use crate::connectors::prelude::*;
+use crate::messages::{inbound::subscription_id, prelude::*};
+use crate::{
+    manager::{log_params, pending_transaction_params},
+    network::{AlchemyNetwork, Capability},
+    subscription::{release, SubscriptionStream},
+};
+
+/// A shareable handle to a raw alchemy connection
+///
+/// The client is `Clone + Send + Sync`, so any number of tasks can issue requests and
+/// subscribe concurrently over one connection. The socket itself is owned by the
+/// connection's background reader task, which the client queues outbound messages to;
+/// responses are matched to their request by id, and notifications routed to their subscription.
+///
+/// Clients are created from a connected [AlchemyManager](crate::manager::AlchemyManager) with
+/// [client](crate::manager::AlchemyManager::client), and keep working across reconnects.
+/// Once the reader task gives up on the connection, every call fails with [Closed](AlchemyConnectionError::Closed).
+///
+/// ## Example
+///
+/// ```rust
+/// use alchemy_rs::prelude::*;
+///
+/// async {
+///     let mut manager = AlchemyManager::from_network(AlchemyNetwork::EthMainnet, "<API_KEY>", None);
+///     manager.connect().await.unwrap();
+///
+///     let client = manager.client().unwrap();
+///     let tasks = (0..4).map(|_| {
+///         let client = client.clone();
+///         tokio::spawn(async move { client.request::<_, U64>("eth_blockNumber", ()).await })
+///     });
+///     for task in tasks {
+///         println!("Current block: {:?}", task.await);
+///     }
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct AlchemyClient {
+    /// The state shared by every clone
+    inner: Arc<ClientInner>,
+}
+
+/// The state shared by every clone of an [AlchemyClient]
+#[derive(Debug)]
+struct ClientInner {
+    /// The reader task's queue of outbound messages
+    outbound: mpsc::UnboundedSender<String>,
+    /// Routes inbound messages to subscriptions and waiting requests
+    router: Arc<Mutex<SubscriptionRouter>>,
+    /// The connections subscriptions are routed by, shared with the other connections of the manager
+    registry: Arc<RouteRegistry>,
+    /// Allocates the ids of outbound json-rpc requests
+    request_ids: Arc<RequestIdAllocator>,
+    /// The time to wait for the response to a json-rpc request
+    request_timeout: Duration,
+    /// The number of notifications buffered for each subscription
+    notification_buffer_size: usize,
+    /// The network the connection serves, if known
+    network: Option<AlchemyNetwork>,
+}
+
+/// Stops waiting on a response when the request fails or is dropped before it is answered
+///
+/// Answered requests are no longer pending, so dropping the guard after the response arrived is a no-op.
+struct PendingGuard<'a> {
+    /// The router the request waits on
+    router: &'a Mutex<SubscriptionRouter>,
+    /// The request id
+    id: u64,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        lock(self.router).cancel(self.id);
+    }
+}
+
+impl AlchemyClient {
+    /// Create a new client queueing messages to a reader task
+    pub(crate) fn new(
+        outbound: mpsc::UnboundedSender<String>,
+        router: Arc<Mutex<SubscriptionRouter>>,
+        registry: Arc<RouteRegistry>,
+        request_ids: Arc<RequestIdAllocator>,
+        request_timeout: Duration,
+        notification_buffer_size: usize,
+        network: Option<AlchemyNetwork>,
+    ) -> Self {
+        Self {
+            inner: Arc::new(ClientInner {
+                outbound,
+                router,
+                registry,
+                request_ids,
+                request_timeout,
+                notification_buffer_size,
+                network,
+            }),
+        }
+    }
+
+    /// The network the connection serves, if known
+    pub fn network(&self) -> Option<AlchemyNetwork> {
+        self.inner.network
+    }
+
+    /// Sends a json-rpc request, returning its typed result
+    ///
+    /// See [AlchemyManager::request](crate::manager::AlchemyManager::request).
+    pub async fn request<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, AlchemyConnectionError> {
+        self.require(Capability::for_method(method))?;
+        let mut response = self.send_and_wait(method, params).await?;
+        let result = response
+            .get_mut("result")
+            .map(serde_json::Value::take)
+            .unwrap_or_default();
+        serde_json::from_value(result).map_err(AlchemyConnectionError::Deserialization)
+    }
+
+    /// Sends a batch of json-rpc calls in a single message, returning the result or json-rpc error of every call
+    ///
+    /// See [AlchemyManager::batch](crate::manager::AlchemyManager::batch).
+    pub async fn batch(
+        &self,
+        batch: &JsonRpcBatch,
+    ) -> Result<Vec<Result<serde_json::Value, JsonRpcError>>, AlchemyConnectionError> {
+        // An empty array is an invalid json-rpc request
+        if batch.is_empty() {
+            return Ok(vec![]);
+        }
+        for (method, _) in &batch.calls {
+            self.require(Capability::for_method(method))?;
+        }
+
+        let inner = &self.inner;
+        let requests = batch.requests(|| inner.request_ids.next_id());
+        let ids = requests.iter().map(|r| r.id).collect::<Vec<_>>();
+        let message_string = match serde_json::to_string(&requests) {
+            Ok(s) => s,
+            Err(e) => return Err(AlchemyConnectionError::Deserialization(e)),
+        };
+
+        // Every element of the response array is routed to its request on its own,
+        // while an error without an id rejects the batch as a whole
+        let (responses, rejection) = {
+            let mut router = lock(&inner.router);
+            let responses = ids.iter().map(|id| router.wait(*id)).collect::<Vec<_>>();
+            (responses, router.wait_batch(&ids))
+        };
+        let _guards = ids
+            .iter()
+            .map(|id| PendingGuard {
+                router: &inner.router,
+                id: *id,
+            })
+            .collect::<Vec<_>>();
+
+        inner
+            .outbound
+            .send(message_string)
+            .map_err(|_| AlchemyConnectionError::Closed)?;
+
+        let answered = async {
+            tokio::select! {
+                biased;
+                responses = futures::future::try_join_all(responses) => {
+                    responses.map(serde_json::Value::Array)
+                }
+                rejection = rejection => rejection,
+            }
+        };
+        let response = match tokio::time::timeout(inner.request_timeout, answered).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(AlchemyConnectionError::Closed),
+            Err(_) => {
+                return Err(AlchemyConnectionError::RequestTimeout(
+                    inner.request_timeout,
+                ))
+            }
+        };
+        correlate_batch(&ids, response)
+    }
+
+    /// Subscribes to pending transactions, returning a stream of the transactions
+    ///
+    /// See [AlchemyManager::subscribe_pending_transactions](crate::manager::AlchemyManager::subscribe_pending_transactions).
+    pub async fn subscribe_pending_transactions(
+        &self,
+        to: Option<Address>,
+        from: Option<Address>,
+    ) -> Result<SubscriptionStream<Transaction>, AlchemyConnectionError> {
+        self.subscribe_stream(pending_transaction_params(to, from))
+            .await
+    }
+
+    /// Subscribes to new block headers with a standard `newHeads` subscription
+    pub async fn subscribe_new_heads(
+        &self,
+    ) -> Result<SubscriptionStream<Block<TxHash>>, AlchemyConnectionError> {
+        self.subscribe_stream(vec!["newHeads".into()]).await
+    }
+
+    /// Subscribes to the logs matching the filter with a standard `logs` subscription
+    ///
+    /// See [AlchemyManager::subscribe_logs](crate::manager::AlchemyManager::subscribe_logs).
+    pub async fn subscribe_logs(
+        &self,
+        filter: &Filter,
+    ) -> Result<SubscriptionStream<Log>, AlchemyConnectionError> {
+        self.subscribe_stream(log_params(filter)?).await
+    }
+
+    /// Subscribes to the hashes of new pending transactions with a standard `newPendingTransactions` subscription
+    pub async fn subscribe_new_pending_transactions(
+        &self,
+    ) -> Result<SubscriptionStream<TxHash>, AlchemyConnectionError> {
+        self.subscribe_stream(vec!["newPendingTransactions".into()])
+            .await
+    }
+
+    /// Subscribes with the given `eth_subscribe` params, returning a stream of the notification results
+    ///
+    /// See [AlchemyManager::subscribe_stream](crate::manager::AlchemyManager::subscribe_stream).
+    pub async fn subscribe_stream<T: DeserializeOwned>(
+        &self,
+        params: Vec<serde_json::Value>,
+    ) -> Result<SubscriptionStream<T>, AlchemyConnectionError> {
+        let (sub_id, receiver) = self.open_subscription(params).await?;
+        Ok(SubscriptionStream::new(
+            sub_id,
+            receiver,
+            self.inner.registry.clone(),
+        ))
+    }
+
+    /// Cancels a subscription with an `eth_unsubscribe`, returning whether the server cancelled it
+    ///
+    /// The subscription's route is removed even if the server fails to respond.
+    pub async fn unsubscribe(&self, sub_id: uuid::Uuid) -> Result<bool, AlchemyConnectionError> {
+        self.inner.registry.unbind(sub_id);
+        let server_id = lock(&self.inner.router)
+            .remove(sub_id)
+            .ok_or(AlchemyConnectionError::UnknownSubscription(sub_id))?;
+
+        let response = self
+            .send_and_wait(
+                OutSocketMethod::Unsubscribe.as_str(),
+                vec![serde_json::Value::String(server_id)],
+            )
+            .await?;
+        match serde_json::from_value::<AlchemyUnsubscribeMessageResult>(response) {
+            Ok(result) => Ok(result.result),
+            Err(e) => Err(AlchemyConnectionError::Deserialization(e)),
+        }
+    }
+
+    /// Issues a subscription and routes its notifications to a new channel
+    pub(crate) async fn open_subscription(
+        &self,
+        params: Vec<serde_json::Value>,
+    ) -> Result<(uuid::Uuid, mpsc::Receiver<serde_json::Value>), AlchemyConnectionError> {
+        self.require(Capability::for_subscription(&params))?;
+        let server_id = self.issue_subscription(&params).await?;
+        let sub_id = self.inner.registry.bind(&server_id, self.binding());
+
+        // Route the subscription's notifications to its own channel
+        let (sender, receiver) = mpsc::channel(self.inner.notification_buffer_size);
+        if let Err(e) = self.route(server_id, sub_id, params, sender) {
+            self.inner.registry.unbind(sub_id);
+            return Err(e);
+        }
+        Ok((sub_id, receiver))
+    }
+
+    /// Re-issues a subscription of another connection, keeping its id and channel
+    ///
+    /// A subscription whose stream was dropped in the meantime is released again right away.
+    pub(crate) async fn adopt_subscription(
+        &self,
+        id: uuid::Uuid,
+        params: Vec<serde_json::Value>,
+        sender: mpsc::Sender<serde_json::Value>,
+    ) -> Result<(), AlchemyConnectionError> {
+        let server_id = self.issue_subscription(&params).await?;
+        self.route(server_id, id, params, sender)?;
+        if !self.inner.registry.rebind(id, self.binding()) {
+            release(id, &self.binding());
+        }
+        Ok(())
+    }
+
+    /// The binding of subscriptions routed by the connection
+    fn binding(&self) -> RouteBinding {
+        RouteBinding {
+            router: Arc::clone(&self.inner.router),
+            outbound: self.inner.outbound.clone(),
+        }
+    }
+
+    /// Route the notifications of the connection's `server_id` to the subscription `id`
+    ///
+    /// Fails with [Closed](AlchemyConnectionError::Closed) if the connection was given up on in the meantime,
+    /// as its routes were already cleared.
+    fn route(
+        &self,
+        server_id: String,
+        id: uuid::Uuid,
+        params: Vec<serde_json::Value>,
+        sender: mpsc::Sender<serde_json::Value>,
+    ) -> Result<(), AlchemyConnectionError> {
+        let mut router = lock(&self.inner.router);
+        router.insert(server_id, id, params, sender);
+        if self.inner.outbound.is_closed() {
+            router.remove(id);
+            return Err(AlchemyConnectionError::Closed);
+        }
+        Ok(())
+    }
+
+    /// Sends an `eth_subscribe`, returning the subscription id issued by the connection
+    async fn issue_subscription(
+        &self,
+        params: &[serde_json::Value],
+    ) -> Result<String, AlchemyConnectionError> {
+        // After sending the message, the alchemy socket should respond with a subscription id
+        // Ex: { "id": 1, "result": "0x79a3295f5d5f4bd7efaac4e1738c7ada", "jsonrpc": "2.0" }
+        let response = self
+            .send_and_wait(OutSocketMethod::Subscribe.as_str(), params)
+            .await?;
+        subscription_id(response).map_err(AlchemyConnectionError::Deserialization)
+    }
+
+    /// Sends a json-rpc request and waits for its response
+    ///
+    /// The request is given a fresh id, so its response is matched regardless of the order responses arrive in.
+    /// Error responses are mapped to an [RpcError](AlchemyConnectionError::RpcError).
+    pub(crate) async fn send_and_wait<P: Serialize>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<serde_json::Value, AlchemyConnectionError> {
+        let inner = &self.inner;
+
+        // Construct the json-rpc request and turn it into stringified json
+        let message = JsonRpcRequest::new(inner.request_ids.next_id(), method, params);
+        let message_string = match serde_json::to_string(&message) {
+            Ok(s) => s,
+            Err(e) => return Err(AlchemyConnectionError::Deserialization(e)),
+        };
+
+        // Wait on the response before sending, it may arrive before we get to wait on it
+        let response = lock(&inner.router).wait(message.id);
+        let _guard = PendingGuard {
+            router: &inner.router,
+            id: message.id,
+        };
+
+        inner
+            .outbound
+            .send(message_string)
+            .map_err(|_| AlchemyConnectionError::Closed)?;
+
+        // The response channel closes once the reader task gives up on the connection
+        let mut response = match tokio::time::timeout(inner.request_timeout, response).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(AlchemyConnectionError::Closed),
+            Err(_) => {
+                return Err(AlchemyConnectionError::RequestTimeout(
+                    inner.request_timeout,
+                ))
+            }
+        };
+
+        match response
+            .get_mut("error")
+            .map(serde_json::Value::take)
+            .filter(|error| !error.is_null())
+        {
+            Some(error) => match serde_json::from_value::<JsonRpcError>(error) {
+                Ok(error) => Err(AlchemyConnectionError::RpcError(error)),
+                Err(e) => Err(AlchemyConnectionError::Deserialization(e)),
+            },
+            None => Ok(response),
+        }
+    }
+
+    /// Fails if the network doesn't serve the capability
+    fn require(&self, capability: Option<Capability>) -> Result<(), AlchemyConnectionError> {
+        match (self.inner.network, capability) {
+            (Some(network), Some(capability)) => network.require(capability),
+            _ => Ok(()),
+        }
+    }
+}
@@ -12,6 +12,25 @@ pub enum AlchemyConnectionError {
     RawSocketError(soketto::handshake::Error),
     /// A Raw Websocket Handshake Error
     RawHandshakeError(handshake::Error),
+    /// The tls handshake over the raw stream failed
+    TlsError(std::io::Error),
+    /// The connection url could not be parsed
+    InvalidUrl(String),
+    /// The connection url scheme is not `ws` or `wss`
+    UnsupportedScheme(String),
+    /// The handshake or http request was rejected because of a missing or invalid api key (status 401 or 403)
+    Unauthorized(u16),
+    /// The handshake or http request was rejected because the api key is rate limited (status 429),
+    /// along with the time the server asked to wait before retrying, if it sent a `Retry-After`
+    RateLimited(Option<std::time::Duration>),
+    /// The handshake was rejected with some other status code
+    HandshakeRejected(u16),
+    /// The handshake was redirected more than the allowed number of times
+    TooManyRedirects(usize),
+    /// The handshake was redirected from a `wss` url to the given `ws` url
+    InsecureRedirect(String),
+    /// The handshake was redirected to the given url on another host, without cross-host redirects allowed
+    CrossHostRedirect(String),
     /// Deserialization Error
     Deserialization(serde_json::Error),
     /// Received an unexpected response type
@@ -35,4 +54,72 @@ pub enum AlchemyConnectionError {
     Unimplemented,
     /// The text response could not be parsed as a string
     InvalidTextString,
+    /// Reconnecting failed after the given number of attempts
+    ReconnectFailed(usize),
+    /// The connection is dead after the given number of consecutive unanswered keepalive pings
+    KeepaliveTimeout(u32),
+    /// The connection is dead after receiving nothing for the given window
+    IdleTimeout(std::time::Duration),
+    /// The connection is dead after a keepalive ping couldn't be written within the given window
+    WriteTimeout(std::time::Duration),
+    /// No open subscription has the given id
+    UnknownSubscription(uuid::Uuid),
+    /// No response to a request arrived within the given timeout
+    RequestTimeout(std::time::Duration),
+    /// The connection could not be established within the given timeout
+    ConnectTimeout(std::time::Duration),
+    /// The network or chain isn't served by the Alchemy API, or has no ethers-rs chain
+    UnknownNetwork(String),
+    /// The connection serves another chain than the configured network (expected, actual chain id)
+    ChainIdMismatch(u64, u64),
+    /// The network doesn't serve the capability, detected before sending anything
+    UnsupportedOnNetwork(crate::network::Capability, crate::network::AlchemyNetwork),
+    /// The manager configuration is invalid
+    InvalidConfig(String),
+    /// The server answered a request with a json-rpc error
+    RpcError(crate::messages::inbound::JsonRpcError),
+    /// An http request failed
+    HttpError(reqwest::Error),
+    /// An http request was answered with an unexpected status code
+    HttpStatus(u16),
+    /// The subscription can't be emulated by polling a filter
+    UnsupportedSubscription(String),
+    /// The response to a batch lacks the response to the request with the given id
+    MissingBatchResponse(u64),
+    /// Every connection of the pool holds the given maximum number of subscriptions
+    PoolExhausted(usize),
+}
+
+impl AlchemyConnectionError {
+    /// Map a rejected handshake status code to its connection error
+    pub fn from_rejection(status_code: u16) -> Self {
+        match status_code {
+            401 | 403 => AlchemyConnectionError::Unauthorized(status_code),
+            429 => AlchemyConnectionError::RateLimited(None),
+            _ => AlchemyConnectionError::HandshakeRejected(status_code),
+        }
+    }
+
+    /// Map an unsuccessful http status code to its connection error
+    pub fn from_http_status(status_code: u16) -> Self {
+        match status_code {
+            401 | 403 => AlchemyConnectionError::Unauthorized(status_code),
+            429 => AlchemyConnectionError::RateLimited(None),
+            _ => AlchemyConnectionError::HttpStatus(status_code),
+        }
+    }
+
+    /// Whether the error means the underlying connection was lost
+    pub fn is_disconnect(&self) -> bool {
+        matches!(
+            self,
+            AlchemyConnectionError::Closed
+                | AlchemyConnectionError::SomeError(_)
+                | AlchemyConnectionError::SendFailed(_)
+                | AlchemyConnectionError::FlushFailed(_)
+                | AlchemyConnectionError::KeepaliveTimeout(_)
+                | AlchemyConnectionError::IdleTimeout(_)
+                | AlchemyConnectionError::WriteTimeout(_)
+        )
+    }
 }
@@ -0,0 +1,483 @@
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::{sync::broadcast, task::JoinHandle, time::MissedTickBehavior};
+
+use super::{
+    errors::AlchemyConnectionError,
+    lock,
+    pending::RequestIdAllocator,
+    reader::{RawConnectionConfig, RawConnectionHandle},
+    registry::RouteRegistry,
+    router::SubscriptionRouter,
+    url::redact_url,
+};
+use crate::{client::AlchemyClient, network::AlchemyNetwork};
+
+/// The default interval between health probes of every endpoint
+pub const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The weight of the latest sample in the moving averages of latency and error rate
+const SAMPLE_WEIGHT: f64 = 0.2;
+
+/// How much the error rate inflates the score of an endpoint
+const ERROR_PENALTY: f64 = 10.0;
+
+/// The number of failover events buffered for slow receivers
+const EVENT_CAPACITY: usize = 64;
+
+/// The configuration of a failover between several endpoints
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailoverConfig {
+    /// The interval between health probes of every endpoint
+    pub probe_interval: Duration,
+    /// The time a probe waits for its response before counting as failed
+    pub probe_timeout: Duration,
+    /// The number of consecutive failures after which an endpoint is dead
+    pub max_failures: u32,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        Self {
+            probe_interval: DEFAULT_PROBE_INTERVAL,
+            probe_timeout: Duration::from_secs(5),
+            max_failures: 2,
+        }
+    }
+}
+
+/// The health of a failover endpoint, fed by probes and requests
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EndpointHealth {
+    /// Whether the endpoint is considered alive
+    pub alive: bool,
+    /// The moving average of the response latency, `None` until the first response
+    pub latency: Option<Duration>,
+    /// The moving average of the error rate, between `0.0` and `1.0`
+    pub error_rate: f64,
+    /// The number of consecutive failures
+    pub consecutive_failures: u32,
+}
+
+impl EndpointHealth {
+    /// Record a response received after the latency
+    pub fn record_success(&mut self, latency: Duration) {
+        self.latency = Some(match self.latency {
+            Some(average) => average.mul_f64(1.0 - SAMPLE_WEIGHT) + latency.mul_f64(SAMPLE_WEIGHT),
+            None => latency,
+        });
+        self.error_rate *= 1.0 - SAMPLE_WEIGHT;
+        self.consecutive_failures = 0;
+        self.alive = true;
+    }
+
+    /// Record a failure, declaring the endpoint dead after `max_failures` in a row
+    pub fn record_failure(&mut self, max_failures: u32) {
+        self.error_rate = self.error_rate * (1.0 - SAMPLE_WEIGHT) + SAMPLE_WEIGHT;
+        self.consecutive_failures = self.consecutive_failures.saturating_add(1);
+        if self.consecutive_failures >= max_failures {
+            self.alive = false;
+        }
+    }
+
+    /// The score of a live endpoint, lower is healthier, or `None` if the endpoint is dead
+    ///
+    /// The score is roughly the latency in seconds, inflated by the error rate.
+    pub fn score(&self) -> Option<f64> {
+        if !self.alive {
+            return None;
+        }
+        let latency = self.latency.unwrap_or_default().as_secs_f64();
+        Some(latency * (1.0 + ERROR_PENALTY * self.error_rate) + self.error_rate)
+    }
+}
+
+/// Emitted whenever the failover switches its active endpoint
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailoverEvent {
+    /// The [redacted](super::url::AlchemyUrl::redacted) url of the endpoint that died
+    pub from: String,
+    /// The [redacted](super::url::AlchemyUrl::redacted) url of the endpoint taking over
+    pub to: String,
+    /// The number of subscriptions moved over
+    pub subscriptions: usize,
+}
+
+/// A failover endpoint, routing the notifications of its own subscriptions
+#[derive(Debug)]
+struct Endpoint {
+    /// The configuration the endpoint is (re)connected with
+    config: RawConnectionConfig,
+    /// The connection actor, `None` until the endpoint could be connected
+    handle: Mutex<Option<RawConnectionHandle>>,
+    /// Routes the endpoint's inbound messages
+    router: Arc<Mutex<SubscriptionRouter>>,
+    /// The endpoint's health
+    health: Mutex<EndpointHealth>,
+}
+
+/// The state shared by a [FailoverConnection] and its monitor task
+#[derive(Debug)]
+struct FailoverState {
+    /// The endpoints, in order of preference
+    endpoints: Vec<Endpoint>,
+    /// The index of the endpoint subscriptions are placed on
+    active: AtomicUsize,
+    /// The failover configuration
+    config: FailoverConfig,
+    /// The endpoints subscriptions are routed by
+    registry: Arc<RouteRegistry>,
+    /// Allocates the ids of outbound json-rpc requests
+    request_ids: Arc<RequestIdAllocator>,
+    /// The time to wait for the response to a json-rpc request
+    request_timeout: Duration,
+    /// The number of notifications buffered for each subscription
+    notification_buffer_size: usize,
+    /// The network the endpoints serve, if known
+    network: Option<AlchemyNetwork>,
+    /// Publishes the failover events
+    events: broadcast::Sender<FailoverEvent>,
+}
+
+/// Raw connections to several endpoints serving the same chain, failing over between them
+///
+/// Every endpoint is connected (and reconnected) on its own, while a monitor task probes each of them
+/// with an `eth_chainId` every [probe_interval](FailoverConfig::probe_interval), tracking its latency,
+/// error rate and liveness in an [EndpointHealth](EndpointHealth). Requests are sent to the healthiest
+/// endpoint, and feed its health in turn.
+///
+/// Subscriptions are placed on the active endpoint, initially the first one that could be connected.
+/// Once the active endpoint is dead, the monitor switches to the healthiest live endpoint, re-issues
+/// every subscription there, keeping its id and channel, and publishes a [FailoverEvent](FailoverEvent).
+///
+/// Dropping the connection stops the monitor and closes every endpoint.
+#[derive(Debug)]
+pub struct FailoverConnection {
+    /// The state shared with the monitor task
+    state: Arc<FailoverState>,
+    /// The monitor task
+    monitor: JoinHandle<()>,
+}
+
+impl FailoverConnection {
+    /// Connect to every endpoint, failing only if none of them could be connected
+    ///
+    /// The endpoints keep reconnecting in the background regardless of their reconnect policy's
+    /// retry limit, while the failover routes around them.
+    pub async fn connect(
+        configs: Vec<RawConnectionConfig>,
+        config: FailoverConfig,
+        registry: Arc<RouteRegistry>,
+        request_ids: Arc<RequestIdAllocator>,
+        request_timeout: Duration,
+        notification_buffer_size: usize,
+        network: Option<AlchemyNetwork>,
+    ) -> Result<Self, AlchemyConnectionError> {
+        let connecting = configs.iter().map(RawConnectionConfig::connect);
+        let connections = futures::future::join_all(connecting).await;
+
+        let mut endpoints = vec![];
+        let mut reason = None;
+        for (mut endpoint_config, connection) in configs.into_iter().zip(connections) {
+            endpoint_config.reconnect_policy.max_retries = None;
+            let router = Arc::new(Mutex::new(SubscriptionRouter::new()));
+            let handle = match connection {
+                Ok(connection) => Some(RawConnectionHandle::spawn(
+                    connection,
+                    endpoint_config.clone(),
+                    Arc::clone(&router),
+                )),
+                Err(e) => {
+                    tracing::warn!(
+                        "Failed to connect to {}: {:?}",
+                        redact_url(&endpoint_config.url),
+                        e
+                    );
+                    reason.get_or_insert(e);
+                    None
+                }
+            };
+            let health = EndpointHealth {
+                alive: handle.is_some(),
+                ..EndpointHealth::default()
+            };
+            endpoints.push(Endpoint {
+                config: endpoint_config,
+                handle: Mutex::new(handle),
+                router,
+                health: Mutex::new(health),
+            });
+        }
+
+        let active = endpoints
+            .iter()
+            .position(|endpoint| lock(&endpoint.health).alive)
+            .ok_or_else(|| reason.unwrap_or(AlchemyConnectionError::MissingConnection))?;
+        let (events, _) = broadcast::channel(EVENT_CAPACITY);
+        let state = Arc::new(FailoverState {
+            endpoints,
+            active: AtomicUsize::new(active),
+            config,
+            registry,
+            request_ids,
+            request_timeout,
+            notification_buffer_size,
+            network,
+            events,
+        });
+        let monitor = tokio::spawn(monitor(Arc::clone(&state)));
+        Ok(Self { state, monitor })
+    }
+
+    /// The urls of the endpoints, in order of preference
+    pub fn urls(&self) -> Vec<String> {
+        self.state
+            .endpoints
+            .iter()
+            .map(|endpoint| endpoint.config.url.clone())
+            .collect()
+    }
+
+    /// The index of the endpoint subscriptions are placed on
+    pub fn active(&self) -> usize {
+        self.state.active.load(Ordering::SeqCst)
+    }
+
+    /// The health of every endpoint
+    pub fn health(&self) -> Vec<EndpointHealth> {
+        self.state
+            .endpoints
+            .iter()
+            .map(|endpoint| lock(&endpoint.health).clone())
+            .collect()
+    }
+
+    /// Subscribe to the events emitted whenever the active endpoint switches
+    pub fn events(&self) -> broadcast::Receiver<FailoverEvent> {
+        self.state.events.subscribe()
+    }
+
+    /// A client bound to the healthiest endpoint
+    pub fn client(&self) -> Result<AlchemyClient, AlchemyConnectionError> {
+        let index = self
+            .state
+            .healthiest()
+            .ok_or(AlchemyConnectionError::Closed)?;
+        self.state
+            .client(index, self.state.request_timeout)
+            .ok_or(AlchemyConnectionError::Closed)
+    }
+
+    /// Clients bound to every connected endpoint, eg to race a subscription on all of them
+    pub fn clients(&self) -> Vec<AlchemyClient> {
+        self.indexed_clients()
+            .into_iter()
+            .map(|(_, client)| client)
+            .collect()
+    }
+
+    /// Clients bound to every connected endpoint, along with the endpoint's index
+    pub fn indexed_clients(&self) -> Vec<(usize, AlchemyClient)> {
+        (0..self.state.endpoints.len())
+            .filter_map(|index| {
+                let client = self.state.client(index, self.state.request_timeout)?;
+                Some((index, client))
+            })
+            .collect()
+    }
+
+    /// A client bound to the active endpoint, to place new subscriptions with
+    pub fn subscription_client(&self) -> Result<AlchemyClient, AlchemyConnectionError> {
+        self.state
+            .client(self.active(), self.state.request_timeout)
+            .ok_or(AlchemyConnectionError::Closed)
+    }
+
+    /// A client bound to the endpoint serving the subscription with the caller's `id`
+    pub fn client_for(&self, id: uuid::Uuid) -> Option<AlchemyClient> {
+        let index = self
+            .state
+            .endpoints
+            .iter()
+            .position(|endpoint| lock(&endpoint.router).contains(id))?;
+        self.state.client(index, self.state.request_timeout)
+    }
+
+    /// Sends a json-rpc request to the healthiest endpoint, recording the outcome in its health
+    ///
+    /// Json-rpc errors are answers like any other, only failing to get an answer counts against the endpoint.
+    pub async fn request<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, AlchemyConnectionError> {
+        let index = self
+            .state
+            .healthiest()
+            .ok_or(AlchemyConnectionError::Closed)?;
+        let client = self
+            .state
+            .client(index, self.state.request_timeout)
+            .ok_or(AlchemyConnectionError::Closed)?;
+
+        let started = Instant::now();
+        let result = client.request(method, params).await;
+        self.state.record(index, &result, started.elapsed());
+        result
+    }
+}
+
+impl Drop for FailoverConnection {
+    fn drop(&mut self) {
+        self.monitor.abort();
+    }
+}
+
+impl Endpoint {
+    /// Whether the endpoint is connected, ie has a connection actor that didn't give up
+    fn is_connected(&self) -> bool {
+        lock(&self.handle)
+            .as_ref()
+            .is_some_and(|handle| !handle.outbound().is_closed())
+    }
+}
+
+impl FailoverState {
+    /// A client bound to the endpoint, if it is connected
+    ///
+    /// Endpoints whose connection actor gave up count as disconnected.
+    fn client(&self, index: usize, request_timeout: Duration) -> Option<AlchemyClient> {
+        let endpoint = self.endpoints.get(index)?;
+        let outbound = lock(&endpoint.handle).as_ref()?.outbound();
+        if outbound.is_closed() {
+            return None;
+        }
+        Some(AlchemyClient::new(
+            outbound,
+            Arc::clone(&endpoint.router),
+            Arc::clone(&self.registry),
+            Arc::clone(&self.request_ids),
+            request_timeout,
+            self.notification_buffer_size,
+            self.network,
+        ))
+    }
+
+    /// The index of the live, connected endpoint with the best score, preferring earlier endpoints on ties
+    fn healthiest(&self) -> Option<usize> {
+        self.endpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, endpoint)| endpoint.is_connected())
+            .filter_map(|(index, endpoint)| Some((index, lock(&endpoint.health).score()?)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// Record the outcome of a request in the endpoint's health
+    fn record<T>(
+        &self,
+        index: usize,
+        result: &Result<T, AlchemyConnectionError>,
+        latency: Duration,
+    ) {
+        let mut health = lock(&self.endpoints[index].health);
+        match result {
+            Ok(_) | Err(AlchemyConnectionError::RpcError(_)) => health.record_success(latency),
+            Err(_) => health.record_failure(self.config.max_failures),
+        }
+    }
+
+    /// Probe the endpoint with an `eth_chainId`, connecting it first if it never was or its actor gave up
+    async fn probe(&self, index: usize) {
+        let endpoint = &self.endpoints[index];
+        let timeout = self.config.probe_timeout;
+        let started = Instant::now();
+
+        if !endpoint.is_connected() {
+            let connection = match tokio::time::timeout(timeout, endpoint.config.connect()).await {
+                Ok(Ok(connection)) => connection,
+                Ok(Err(e)) => return self.record::<()>(index, &Err(e), started.elapsed()),
+                Err(_) => {
+                    let reason = AlchemyConnectionError::ConnectTimeout(timeout);
+                    return self.record::<()>(index, &Err(reason), started.elapsed());
+                }
+            };
+            let handle = RawConnectionHandle::spawn(
+                connection,
+                endpoint.config.clone(),
+                Arc::clone(&endpoint.router),
+            );
+            *lock(&endpoint.handle) = Some(handle);
+        }
+
+        let client = match self.client(index, timeout) {
+            Some(client) => client,
+            None => return,
+        };
+        let result = client
+            .request::<_, serde_json::Value>("eth_chainId", ())
+            .await;
+        self.record(index, &result, started.elapsed());
+    }
+
+    /// Switch to the healthiest live endpoint once the active endpoint is dead, moving every subscription over
+    async fn failover(&self) {
+        let from = self.active.load(Ordering::SeqCst);
+        if lock(&self.endpoints[from].health).alive {
+            return;
+        }
+        let to = match self.healthiest() {
+            Some(to) if to != from => to,
+            _ => return,
+        };
+        let client = match self.client(to, self.request_timeout) {
+            Some(client) => client,
+            None => return,
+        };
+
+        // New subscriptions go to the new endpoint while the existing ones are moved
+        self.active.store(to, Ordering::SeqCst);
+        let (from, to) = (&self.endpoints[from], &self.endpoints[to]);
+        let (from_url, to_url) = (redact_url(&from.config.url), redact_url(&to.config.url));
+        tracing::warn!("Failing over from {} to {}", from_url, to_url);
+
+        let routes = lock(&from.router).drain();
+        let mut moved = 0;
+        for (id, params, sender) in routes {
+            // Streams dropped in the meantime aren't worth re-issuing
+            if sender.is_closed() {
+                continue;
+            }
+            match client.adopt_subscription(id, params, sender).await {
+                Ok(()) => moved += 1,
+                Err(e) => tracing::warn!("Failed to move subscription {}: {:?}", id, e),
+            }
+        }
+
+        let _ = self.events.send(FailoverEvent {
+            from: from_url,
+            to: to_url,
+            subscriptions: moved,
+        });
+    }
+}
+
+/// Probes every endpoint every probe interval, failing over once the active endpoint is dead
+async fn monitor(state: Arc<FailoverState>) {
+    let mut ticker = tokio::time::interval(state.config.probe_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let probes = (0..state.endpoints.len()).map(|index| state.probe(index));
+        futures::future::join_all(probes).await;
+        state.failover().await;
+    }
+}
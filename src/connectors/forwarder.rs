@@ -0,0 +1,53 @@
+use std::future::Future;
+
+use tokio::{sync::oneshot, task::JoinHandle};
+
+use super::errors::AlchemyConnectionError;
+
+/// A background task forwarding the notifications of a subscription to its channel, eg over a provider
+///
+/// The task cancels its subscription on the server once [stopped](Forwarder::stop),
+/// or once the receiving end of its channel is dropped.
+#[derive(Debug)]
+pub struct Forwarder {
+    /// Tells the task to cancel its subscription
+    stop: oneshot::Sender<()>,
+    /// The task, resolving to whether the server cancelled the subscription
+    task: JoinHandle<Result<bool, AlchemyConnectionError>>,
+}
+
+impl Forwarder {
+    /// Spawn the task, handing it the receiving end of its stop signal
+    pub fn spawn<F, Fut>(forward: F) -> Self
+    where
+        F: FnOnce(oneshot::Receiver<()>) -> Fut,
+        Fut: Future<Output = Result<bool, AlchemyConnectionError>> + Send + 'static,
+    {
+        let (stop, stopped) = oneshot::channel();
+        Self {
+            stop,
+            task: tokio::spawn(forward(stopped)),
+        }
+    }
+
+    /// Whether the task ended, eg after the subscription ended on its own
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// Stop the task, waiting for it to cancel the subscription
+    ///
+    /// ## Returns
+    ///
+    /// Whether the server cancelled the subscription, `false` if it had already ended.
+    pub async fn stop(self) -> Result<bool, AlchemyConnectionError> {
+        let _ = self.stop.send(());
+        match self.task.await {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!("Forwarding task failed: {:?}", e);
+                Err(AlchemyConnectionError::Closed)
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::{sync::Arc, time::Duration};
+
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use super::{
+    errors::AlchemyConnectionError,
+    pending::{correlate_batch, RequestIdAllocator, DEFAULT_REQUEST_TIMEOUT},
+};
+use crate::messages::{
+    inbound::{JsonRpcError, JsonRpcResponse},
+    outbound::{JsonRpcBatch, JsonRpcRequest},
+};
+
+/// The default time idle pooled connections are kept open
+pub const DEFAULT_POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// The default maximum number of idle connections kept open per host
+pub const DEFAULT_MAX_IDLE_CONNECTIONS: usize = 8;
+
+/// The interval of tcp keepalive probes on pooled connections
+const TCP_KEEPALIVE: Duration = Duration::from_secs(60);
+
+/// The configuration an [HttpConnection](HttpConnection) is created with
+#[derive(Debug, Clone)]
+pub struct HttpConnectionConfig {
+    /// The `http://` or `https://` url
+    pub url: String,
+    /// The time allowed for a request, including reading the response
+    pub request_timeout: Duration,
+    /// The time allowed to open a new connection, unlimited if `None`
+    pub connect_timeout: Option<Duration>,
+    /// Additional headers sent with every request
+    pub headers: Vec<(String, String)>,
+    /// The time idle pooled connections are kept open
+    pub pool_idle_timeout: Duration,
+    /// The maximum number of idle connections kept open per host
+    pub max_idle_connections: usize,
+}
+
+impl HttpConnectionConfig {
+    /// Create a new configuration for the url with default settings
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            connect_timeout: None,
+            headers: vec![],
+            pool_idle_timeout: DEFAULT_POOL_IDLE_TIMEOUT,
+            max_idle_connections: DEFAULT_MAX_IDLE_CONNECTIONS,
+        }
+    }
+
+    /// Create the connection
+    ///
+    /// No request is sent yet, connections are opened on demand.
+    pub fn connect(&self) -> Result<HttpConnection, AlchemyConnectionError> {
+        let url = parse_http_url(&self.url)?;
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| {
+                AlchemyConnectionError::InvalidConfig(format!("invalid header name: {:?}", name))
+            })?;
+            let value = HeaderValue::from_str(value).map_err(|_| {
+                AlchemyConnectionError::InvalidConfig(format!("invalid header value: {:?}", value))
+            })?;
+            headers.insert(name, value);
+        }
+
+        let mut builder = reqwest::Client::builder()
+            .gzip(true)
+            .default_headers(headers)
+            .timeout(self.request_timeout)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .pool_max_idle_per_host(self.max_idle_connections)
+            .tcp_keepalive(TCP_KEEPALIVE);
+        if let Some(timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        let client = builder.build().map_err(AlchemyConnectionError::HttpError)?;
+
+        Ok(HttpConnection {
+            url,
+            client,
+            request_ids: Arc::new(RequestIdAllocator::new()),
+        })
+    }
+}
+
+/// A json-rpc connection over http, eg for Alchemy's request/response only apis
+///
+/// Connections are kept alive and reused across requests, gzip-compressed responses are decoded transparently,
+/// and several calls can be sent in a single request with [batch](HttpConnection::batch).
+/// Requests and responses use the same json-rpc types as the websocket connectors.
+///
+/// The connection is cheap to clone, and clones share the connection pool.
+#[derive(Debug, Clone)]
+pub struct HttpConnection {
+    /// The endpoint url
+    url: Url,
+    /// The http client, pooling connections
+    client: reqwest::Client,
+    /// Allocates the ids of outbound json-rpc requests
+    request_ids: Arc<RequestIdAllocator>,
+}
+
+impl HttpConnection {
+    /// The endpoint url
+    pub fn url(&self) -> &str {
+        self.url.as_str()
+    }
+
+    /// Sends a json-rpc request, returning its typed result
+    ///
+    /// Json-rpc errors are mapped to an [RpcError](AlchemyConnectionError::RpcError).
+    pub async fn request<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, AlchemyConnectionError> {
+        let request = JsonRpcRequest::new(self.request_ids.next_id(), method, params);
+        let response = serde_json::from_value::<JsonRpcResponse>(self.post(&request).await?)
+            .map_err(AlchemyConnectionError::Deserialization)?;
+        match response.into_result() {
+            Ok(result) => {
+                serde_json::from_value(result).map_err(AlchemyConnectionError::Deserialization)
+            }
+            Err(error) => Err(AlchemyConnectionError::RpcError(error)),
+        }
+    }
+
+    /// Sends a batch of json-rpc calls in a single request
+    ///
+    /// ## Returns
+    ///
+    /// The result or json-rpc error of every call, in the order of the calls.
+    pub async fn batch(
+        &self,
+        batch: &JsonRpcBatch,
+    ) -> Result<Vec<Result<serde_json::Value, JsonRpcError>>, AlchemyConnectionError> {
+        // An empty array is an invalid json-rpc request
+        if batch.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let requests = batch.requests(|| self.request_ids.next_id());
+        let ids = requests.iter().map(|r| r.id).collect::<Vec<_>>();
+        let response = self.post(&requests).await?;
+        correlate_batch(&ids, response)
+    }
+
+    /// Posts the json body, returning the json response
+    ///
+    /// Rate limited responses are mapped to [RateLimited](AlchemyConnectionError::RateLimited) whatever their body,
+    /// along with their `Retry-After`. Other unsuccessful responses carrying a json-rpc error are mapped to an
+    /// [RpcError](AlchemyConnectionError::RpcError).
+    async fn post<B: Serialize + ?Sized>(
+        &self,
+        body: &B,
+    ) -> Result<serde_json::Value, AlchemyConnectionError> {
+        let response = self
+            .client
+            .post(self.url.clone())
+            .json(body)
+            .send()
+            .await
+            .map_err(AlchemyConnectionError::HttpError)?;
+
+        let status = response.status();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return Err(AlchemyConnectionError::RateLimited(retry_after(
+                response.headers(),
+            )));
+        }
+        if !status.is_success() {
+            let body = response.bytes().await.unwrap_or_default();
+            return Err(match serde_json::from_slice::<JsonRpcResponse>(&body) {
+                Ok(JsonRpcResponse {
+                    error: Some(error), ..
+                }) => AlchemyConnectionError::RpcError(error),
+                _ => AlchemyConnectionError::from_http_status(status.as_u16()),
+            });
+        }
+
+        response
+            .json()
+            .await
+            .map_err(AlchemyConnectionError::HttpError)
+    }
+}
+
+/// The delay of a `Retry-After` header given in seconds
+///
+/// Http dates aren't supported, as Alchemy only ever sends seconds.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let seconds = headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+/// Parses an `http://` or `https://` url
+pub fn parse_http_url(url: &str) -> Result<Url, AlchemyConnectionError> {
+    let url = Url::parse(url).map_err(|_| AlchemyConnectionError::InvalidUrl(url.to_string()))?;
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        scheme => Err(AlchemyConnectionError::UnsupportedScheme(
+            scheme.to_string(),
+        )),
+    }
+}
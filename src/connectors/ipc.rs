@@ -0,0 +1,283 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+};
+
+use super::{errors::AlchemyConnectionError, lock, reader::dispatch, router::SubscriptionRouter};
+
+/// The size of the chunks read from the socket
+const READ_CHUNK_SIZE: usize = 8 * 1024;
+
+/// A handle to a json-rpc connection over a unix domain socket, eg the ipc endpoint of a local node
+///
+/// Like a [RawConnectionHandle](super::reader::RawConnectionHandle), the connection is owned by a background actor:
+/// a writer task sends the messages queued with [send](IpcConnectionHandle::send),
+/// while a reader task splits the inbound byte stream into json values and dispatches them through the shared
+/// [SubscriptionRouter](SubscriptionRouter), so subscriptions and requests behave the same as over a websocket.
+///
+/// The connection isn't re-established once it drops, every route is then removed, ending all subscriptions.
+///
+/// Dropping the handle closes the connection the same way, even while clients still hold a clone of the outbound queue.
+#[derive(Debug)]
+pub struct IpcConnectionHandle {
+    /// The queue of outbound messages
+    outbound: mpsc::UnboundedSender<String>,
+    /// Tells the actor to close the connection, fired on drop
+    shutdown: Option<oneshot::Sender<()>>,
+    /// The actor task, resolving to the reason the connection ended
+    task: Option<JoinHandle<AlchemyConnectionError>>,
+}
+
+impl IpcConnectionHandle {
+    /// Connect to the unix domain socket at the path
+    #[cfg(unix)]
+    pub async fn connect(
+        path: &str,
+        router: Arc<Mutex<SubscriptionRouter>>,
+    ) -> Result<Self, AlchemyConnectionError> {
+        match tokio::net::UnixStream::connect(path).await {
+            Ok(stream) => Ok(Self::spawn(stream, router)),
+            Err(e) => Err(AlchemyConnectionError::RawStreamError(e)),
+        }
+    }
+
+    /// Connect to the unix domain socket at the path, unsupported on this platform
+    #[cfg(not(unix))]
+    pub async fn connect(
+        _path: &str,
+        _router: Arc<Mutex<SubscriptionRouter>>,
+    ) -> Result<Self, AlchemyConnectionError> {
+        Err(AlchemyConnectionError::Unimplemented)
+    }
+
+    /// Spawn the connection actor for an established stream
+    pub fn spawn<S>(stream: S, router: Arc<Mutex<SubscriptionRouter>>) -> Self
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (outbound, outbound_rx) = mpsc::unbounded_channel();
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let task = tokio::spawn(run(stream, router, outbound_rx, shutdown_rx));
+        Self {
+            outbound,
+            shutdown: Some(shutdown),
+            task: Some(task),
+        }
+    }
+
+    /// Queue a text message to be sent over the socket
+    pub fn send(&self, text: String) -> Result<(), AlchemyConnectionError> {
+        self.outbound
+            .send(text)
+            .map_err(|_| AlchemyConnectionError::Closed)
+    }
+
+    /// A clone of the outbound queue, eg for subscription handles
+    pub fn outbound(&self) -> mpsc::UnboundedSender<String> {
+        self.outbound.clone()
+    }
+
+    /// Close the connection, waiting until every route is removed
+    pub async fn close(mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        self.closed().await;
+    }
+
+    /// Wait for the connection to end, returning the reason
+    ///
+    /// Resolves immediately with [Closed](AlchemyConnectionError::Closed) once the reason was taken.
+    pub async fn closed(&mut self) -> AlchemyConnectionError {
+        let reason = match self.task.as_mut() {
+            Some(task) => match task.await {
+                Ok(reason) => reason,
+                Err(e) => {
+                    tracing::warn!("Connection actor failed: {:?}", e);
+                    AlchemyConnectionError::Closed
+                }
+            },
+            None => return AlchemyConnectionError::Closed,
+        };
+        self.task = None;
+        reason
+    }
+}
+
+impl Drop for IpcConnectionHandle {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Runs the writer and reader tasks until either of them ends or the handle is dropped, then removes every route
+async fn run<S>(
+    stream: S,
+    router: Arc<Mutex<SubscriptionRouter>>,
+    outbound_rx: mpsc::UnboundedReceiver<String>,
+    shutdown: oneshot::Receiver<()>,
+) -> AlchemyConnectionError
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (reader, writer) = tokio::io::split(stream);
+    let mut writer_task = tokio::spawn(write(writer, outbound_rx, shutdown));
+    let mut reader_task = tokio::spawn(read(reader, Arc::clone(&router)));
+
+    let reason = tokio::select! {
+        res = &mut writer_task => {
+            reader_task.abort();
+            match res {
+                Ok(Some(reason)) => reason,
+                // The handle was dropped, the routes end along with the connection
+                Ok(None) => AlchemyConnectionError::Closed,
+                Err(e) => {
+                    tracing::warn!("Writer task failed: {:?}", e);
+                    AlchemyConnectionError::Closed
+                }
+            }
+        }
+        res = &mut reader_task => {
+            // Wait for the outbound queue to close, so no subscription is routed once the routes are cleared
+            writer_task.abort();
+            let _ = writer_task.await;
+            match res {
+                Ok(reason) => reason,
+                Err(e) => {
+                    tracing::warn!("Reader task failed: {:?}", e);
+                    AlchemyConnectionError::Closed
+                }
+            }
+        }
+    };
+
+    tracing::warn!("Ipc connection ended: {:?}", reason);
+    lock(&router).clear();
+    reason
+}
+
+/// Writes the queued outbound messages, newline-delimited, until the handle is dropped
+async fn write<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    mut outbound_rx: mpsc::UnboundedReceiver<String>,
+    mut shutdown: oneshot::Receiver<()>,
+) -> Option<AlchemyConnectionError> {
+    loop {
+        let mut text = tokio::select! {
+            biased;
+            _ = &mut shutdown => break,
+            Some(text) = outbound_rx.recv() => text,
+        };
+        text.push('\n');
+        let written = async {
+            writer.write_all(text.as_bytes()).await?;
+            writer.flush().await
+        };
+        if let Err(e) = written.await {
+            return Some(AlchemyConnectionError::RawStreamError(e));
+        }
+    }
+
+    tracing::debug!("Connection handle dropped, closing the socket");
+    if let Err(e) = writer.shutdown().await {
+        tracing::debug!("Failed to close the socket: {:?}", e);
+    }
+    None
+}
+
+/// Dispatches the json values read from the socket until it closes
+async fn read<R: AsyncRead + Unpin>(
+    mut reader: R,
+    router: Arc<Mutex<SubscriptionRouter>>,
+) -> AlchemyConnectionError {
+    let mut framer = JsonFramer::default();
+    let mut chunk = vec![0; READ_CHUNK_SIZE];
+    loop {
+        let read = match reader.read(&mut chunk).await {
+            Ok(0) => return AlchemyConnectionError::Closed,
+            Ok(read) => read,
+            Err(e) => return AlchemyConnectionError::RawStreamError(e),
+        };
+        framer.push(&chunk[..read], |text| dispatch(&router, text));
+    }
+}
+
+/// Splits a byte stream of json objects and arrays into the text of each value
+///
+/// Values aren't necessarily delimited, so the framer tracks the nesting of the value being read
+/// across pushes. Every byte is only scanned once, however many reads a value spans.
+///
+/// Stray bytes between values are skipped up to the next `{` or `[`, and values that aren't valid utf-8 are dropped,
+/// so a corrupted value doesn't end the connection.
+#[derive(Debug, Default)]
+struct JsonFramer {
+    /// The bytes of the value being read
+    buffer: Vec<u8>,
+    /// The number of bytes of the buffer already scanned
+    scanned: usize,
+    /// The nesting depth at the end of the scanned bytes
+    depth: usize,
+    /// Whether the scanned bytes end within a string
+    in_string: bool,
+    /// Whether the scanned bytes end with an escaping backslash within a string
+    escaped: bool,
+}
+
+impl JsonFramer {
+    /// Append the bytes, handing the text of every value they complete to `on_value`
+    fn push(&mut self, bytes: &[u8], mut on_value: impl FnMut(&str)) {
+        self.buffer.extend_from_slice(bytes);
+
+        let mut start = 0;
+        let mut skipped = 0;
+        for index in self.scanned..self.buffer.len() {
+            let byte = self.buffer[index];
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => (),
+                }
+                continue;
+            }
+
+            match byte {
+                b'{' | b'[' => {
+                    if self.depth == 0 {
+                        start = index;
+                    }
+                    self.depth += 1;
+                }
+                b'}' | b']' if self.depth > 0 => {
+                    self.depth -= 1;
+                    if self.depth == 0 {
+                        match std::str::from_utf8(&self.buffer[start..=index]) {
+                            Ok(text) => on_value(text),
+                            Err(e) => tracing::warn!("Dropping value that isn't utf-8: {:?}", e),
+                        }
+                        start = index + 1;
+                    }
+                }
+                b'"' if self.depth > 0 => self.in_string = true,
+                _ if self.depth > 0 || byte.is_ascii_whitespace() => (),
+                _ => skipped += 1,
+            }
+        }
+        if skipped > 0 {
+            tracing::warn!("Skipped {} stray bytes between json values", skipped);
+        }
+
+        // Keep only the value being read, skipping the whitespace after the last complete one
+        if self.depth == 0 {
+            start = self.buffer.len();
+        }
+        self.buffer.drain(..start);
+        self.scanned = self.buffer.len();
+    }
+}
@@ -0,0 +1,121 @@
+use std::time::{Duration, Instant};
+
+use super::errors::AlchemyConnectionError;
+
+/// The payload sent with every keepalive ping
+pub const KEEPALIVE_PING_PAYLOAD: &[u8] = b"alchemy-rs";
+
+/// Websocket keepalive configuration
+///
+/// Pings are sent every `ping_interval`. The connection is declared dead once
+/// `max_missed_pongs` pings in a row go unanswered, or if nothing at all is received
+/// for the `idle_timeout` window, or if a ping can't be written within the `write_timeout`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeepaliveConfig {
+    /// The interval between pings
+    pub ping_interval: Duration,
+    /// The number of consecutive unanswered pings after which the connection is dead
+    pub max_missed_pongs: u32,
+    /// The optional window of silence (no frames at all) after which the connection is dead
+    pub idle_timeout: Option<Duration>,
+    /// The time allowed to write and flush a ping before the connection is dead
+    pub write_timeout: Duration,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            ping_interval: Duration::from_secs(30),
+            max_missed_pongs: 2,
+            idle_timeout: Some(Duration::from_secs(120)),
+            write_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+impl KeepaliveConfig {
+    /// How often the keepalive state is checked while waiting for a frame
+    pub fn check_interval(&self) -> Duration {
+        match self.idle_timeout {
+            Some(idle) => self.ping_interval.min(idle),
+            None => self.ping_interval,
+        }
+    }
+}
+
+/// The keepalive bookkeeping of a connection
+#[derive(Debug, Clone)]
+pub struct KeepaliveState {
+    /// When the last frame of any kind was received
+    pub last_activity: Instant,
+    /// When the last ping was sent
+    pub last_ping: Instant,
+    /// Whether the last ping is still awaiting its pong
+    pub awaiting_pong: bool,
+    /// The number of consecutive unanswered pings
+    pub missed_pongs: u32,
+    /// The round-trip time of the last answered ping
+    pub last_rtt: Option<Duration>,
+}
+
+impl Default for KeepaliveState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl KeepaliveState {
+    /// Create a fresh keepalive state, as of a newly established connection
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            last_activity: now,
+            last_ping: now,
+            awaiting_pong: false,
+            missed_pongs: 0,
+            last_rtt: None,
+        }
+    }
+
+    /// Record that a frame was received
+    pub fn on_frame(&mut self) {
+        self.last_activity = Instant::now();
+    }
+
+    /// Record that a pong was received
+    pub fn on_pong(&mut self) {
+        self.on_frame();
+        if self.awaiting_pong {
+            self.last_rtt = Some(self.last_activity.duration_since(self.last_ping));
+            tracing::trace!("Received pong after {:?}", self.last_rtt);
+        }
+        self.awaiting_pong = false;
+        self.missed_pongs = 0;
+    }
+
+    /// Check the connection's liveness, returning whether a new ping is due
+    pub fn check(&mut self, config: &KeepaliveConfig) -> Result<bool, AlchemyConnectionError> {
+        let now = Instant::now();
+        if let Some(idle) = config.idle_timeout {
+            if now.duration_since(self.last_activity) >= idle {
+                return Err(AlchemyConnectionError::IdleTimeout(idle));
+            }
+        }
+        if now.duration_since(self.last_ping) < config.ping_interval {
+            return Ok(false);
+        }
+        if self.awaiting_pong {
+            self.missed_pongs += 1;
+            if self.missed_pongs >= config.max_missed_pongs {
+                return Err(AlchemyConnectionError::KeepaliveTimeout(self.missed_pongs));
+            }
+        }
+        Ok(true)
+    }
+
+    /// Record that a ping was sent
+    pub fn on_ping(&mut self) {
+        self.last_ping = Instant::now();
+        self.awaiting_pong = true;
+    }
+}
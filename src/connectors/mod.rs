@@ -6,12 +6,71 @@ pub mod provider;
 /// A raw websocket connector
 pub mod raw;
 
+/// Websocket url parsing
+pub mod url;
+
+/// The byte streams underlying raw connections
+pub mod stream;
+
+/// Pluggable transports opening the streams of raw connections
+pub mod transport;
+
+/// Reconnection backoff policies
+pub mod reconnect;
+
+/// Websocket ping/pong keepalive
+pub mod keepalive;
+
+/// Inbound message routing
+pub mod router;
+
+/// The connections subscriptions are routed by
+pub mod registry;
+
+/// Background tasks forwarding subscriptions the connection doesn't route itself
+pub mod forwarder;
+
+/// Json-rpc request ids and responses
+pub mod pending;
+
+/// The background reader task of raw connections
+pub mod reader;
+
+/// A json-rpc connector over unix domain sockets
+pub mod ipc;
+
+/// A json-rpc connector over http
+pub mod http;
+
+/// Subscriptions emulated by polling filters over http
+pub mod polling;
+
+/// A pool of raw connections sharing many subscriptions
+pub mod pool;
+
+/// Failover between several endpoints
+pub mod failover;
+
 /// Common Errors
 pub mod errors;
 
+/// Lock the mutex, recovering its data if a holder panicked
+///
+/// Every lock of the connectors guards state a panicking holder leaves consistent, eg routes or
+/// keepalive bookkeeping, so poisoning is ignored rather than failing every later caller.
+pub(crate) fn lock<T>(mutex: &std::sync::Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
 /// Re-export a prelude
 pub mod prelude {
-    pub use super::{errors::*, provider::*, raw::*, *};
+    pub use super::{
+        errors::*, failover::*, forwarder::*, http::*, ipc::*, keepalive::*, pending::*,
+        polling::*, pool::*, provider::*, raw::*, reader::*, reconnect::*, registry::*, router::*,
+        stream::*, transport::*, url::*, *,
+    };
 }
 
 /// An alchemy api connection manager
@@ -19,8 +78,17 @@ pub mod prelude {
 pub enum AlchemyConnector {
     /// An ethers-rs websocket [Provider](ethers::providers::Provider) for alchemy
     Provider(Option<provider::EthersWsProvider>),
-    /// A Raw, Persistent Websocket Connection to the Alchemy API using [soketto](https://docs.rs/soketto/latest/soketto/)
-    Raw(Option<raw::RawAlchemyConnection>),
+    /// A Raw, Persistent Websocket Connection to the Alchemy API using [soketto](https://docs.rs/soketto/latest/soketto/),
+    /// owned by a background reader task
+    Raw(Option<reader::RawConnectionHandle>),
+    /// A json-rpc connection over a unix domain socket, eg to a local node, owned by a background actor
+    Ipc(Option<ipc::IpcConnectionHandle>),
+    /// A json-rpc connection over http, polling filters in place of subscriptions
+    Http(Option<http::HttpConnection>),
+    /// A pool of raw connections, spreading subscriptions over several websockets
+    Pool(Option<pool::ConnectionPool>),
+    /// Raw connections to a primary and backup endpoints, failing over to the healthiest one
+    Failover(Option<failover::FailoverConnection>),
 }
 
 /// The type of alchemy api websocket connection
@@ -30,6 +98,14 @@ pub enum AlchemyConnectorType {
     Provider,
     /// A Raw, Persistent Websocket Connection to the Alchemy API using [soketto](https://docs.rs/soketto/latest/soketto/)
     Raw,
+    /// A json-rpc connection over a unix domain socket, eg to a local node
+    Ipc,
+    /// A json-rpc connection over http, polling filters in place of subscriptions
+    Http,
+    /// A pool of raw connections, spreading subscriptions over several websockets
+    Pool,
+    /// Raw connections to a primary and backup endpoints, failing over to the healthiest one
+    Failover,
 }
 
 impl Default for AlchemyConnectorType {
@@ -43,6 +119,10 @@ impl From<AlchemyConnectorType> for AlchemyConnector {
         match t {
             AlchemyConnectorType::Provider => AlchemyConnector::Provider(None),
             AlchemyConnectorType::Raw => AlchemyConnector::Raw(None),
+            AlchemyConnectorType::Ipc => AlchemyConnector::Ipc(None),
+            AlchemyConnectorType::Http => AlchemyConnector::Http(None),
+            AlchemyConnectorType::Pool => AlchemyConnector::Pool(None),
+            AlchemyConnectorType::Failover => AlchemyConnector::Failover(None),
         }
     }
 }
@@ -0,0 +1,168 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use tokio::sync::oneshot;
+
+use super::errors::AlchemyConnectionError;
+use crate::messages::inbound::{JsonRpcError, JsonRpcResponse};
+
+/// The first json-rpc id handed out by a [RequestIdAllocator], lower ids are reserved
+pub const FIRST_REQUEST_ID: u64 = 1;
+
+/// The default time to wait for a json-rpc response
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A monotonic json-rpc request id allocator
+///
+/// Ids below [FIRST_REQUEST_ID] and `u64::MAX` are reserved for requests nobody waits on,
+/// like the `eth_unsubscribe`s of dropped subscription streams.
+#[derive(Debug)]
+pub struct RequestIdAllocator {
+    /// The next id to hand out
+    next: AtomicU64,
+}
+
+impl Default for RequestIdAllocator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestIdAllocator {
+    /// Create a new allocator starting at [FIRST_REQUEST_ID]
+    pub fn new() -> Self {
+        Self {
+            next: AtomicU64::new(FIRST_REQUEST_ID),
+        }
+    }
+
+    /// Allocate the next request id
+    pub fn next_id(&self) -> u64 {
+        loop {
+            let id = self.next.fetch_add(1, Ordering::Relaxed);
+            if id >= FIRST_REQUEST_ID && id != u64::MAX {
+                return id;
+            }
+        }
+    }
+}
+
+/// The json-rpc requests waiting on their response, keyed by request id
+///
+/// Batches additionally wait on an error rejecting them as a whole, which carries no request id.
+#[derive(Debug, Default)]
+pub struct PendingRequests {
+    /// The channels responses are delivered on
+    requests: HashMap<u64, oneshot::Sender<serde_json::Value>>,
+    /// The request ids of every waiting batch, oldest first, along with the channel rejections are delivered on
+    batches: VecDeque<(Vec<u64>, oneshot::Sender<serde_json::Value>)>,
+}
+
+impl PendingRequests {
+    /// Create an empty pending request table
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait on the response to the request id
+    pub fn insert(&mut self, id: u64) -> oneshot::Receiver<serde_json::Value> {
+        let (sender, receiver) = oneshot::channel();
+        self.requests.insert(id, sender);
+        receiver
+    }
+
+    /// Wait on an error rejecting the batch of request ids as a whole
+    ///
+    /// The ids must be [waited on](PendingRequests::insert) on their own too, the batch is forgotten once none is.
+    pub fn insert_batch(&mut self, ids: &[u64]) -> oneshot::Receiver<serde_json::Value> {
+        self.forget_answered_batches();
+        let (sender, receiver) = oneshot::channel();
+        self.batches.push_back((ids.to_vec(), sender));
+        receiver
+    }
+
+    /// Deliver an error without a request id to the oldest batch still waiting on a response,
+    /// returning whether there was one
+    pub fn reject(&mut self, error: serde_json::Value) -> bool {
+        self.forget_answered_batches();
+        match self.batches.pop_front() {
+            Some((_, sender)) => sender.send(error).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Forget the batches none of whose requests is still waiting on its response
+    fn forget_answered_batches(&mut self) {
+        let requests = &self.requests;
+        self.batches
+            .retain(|(ids, _)| ids.iter().any(|id| requests.contains_key(id)));
+    }
+
+    /// Stop waiting on the response to the request id, eg after a timeout
+    pub fn remove(&mut self, id: u64) -> bool {
+        self.requests.remove(&id).is_some()
+    }
+
+    /// Deliver a response to its request, returning whether anyone was waiting on it
+    pub fn resolve(&mut self, id: u64, response: serde_json::Value) -> bool {
+        match self.requests.remove(&id) {
+            Some(sender) => sender.send(response).is_ok(),
+            None => false,
+        }
+    }
+
+    /// The number of requests waiting on their response
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Whether no request is waiting on its response
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Stop waiting on every response, failing the waiting requests
+    pub fn clear(&mut self) {
+        self.requests.clear();
+        self.batches.clear();
+    }
+}
+
+/// Matches the response to a batch of requests back to the request ids, returning the per-request results in order
+///
+/// A single error response in place of the array (eg for a malformed batch) fails the whole batch.
+pub fn correlate_batch(
+    ids: &[u64],
+    response: serde_json::Value,
+) -> Result<Vec<Result<serde_json::Value, JsonRpcError>>, AlchemyConnectionError> {
+    let responses = match response {
+        serde_json::Value::Array(responses) => responses,
+        response => {
+            let response = serde_json::from_value::<JsonRpcResponse>(response)
+                .map_err(AlchemyConnectionError::Deserialization)?;
+            return Err(match response.error {
+                Some(error) => AlchemyConnectionError::RpcError(error),
+                None => AlchemyConnectionError::UnexpectedResponseType,
+            });
+        }
+    };
+
+    let mut by_id = HashMap::with_capacity(responses.len());
+    for response in responses {
+        let response = serde_json::from_value::<JsonRpcResponse>(response)
+            .map_err(AlchemyConnectionError::Deserialization)?;
+        if let Some(id) = response.id {
+            by_id.insert(id, response);
+        }
+    }
+
+    ids.iter()
+        .map(|id| match by_id.remove(id) {
+            Some(response) => Ok(response.into_result()),
+            None => Err(AlchemyConnectionError::MissingBatchResponse(*id)),
+        })
+        .collect()
+}
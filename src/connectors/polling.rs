@@ -0,0 +1,287 @@
+use std::time::Duration;
+
+use ethers::types::{Address, Transaction};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::{Instant, MissedTickBehavior},
+};
+
+use super::{errors::AlchemyConnectionError, forwarder::Forwarder, http::HttpConnection};
+use crate::messages::inbound::{format_uuid_simple, JsonRpcError};
+
+/// The default interval subscription filters are polled at
+pub const DEFAULT_FILTER_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A subscription emulated by polling a filter over http
+#[derive(Debug, Clone, PartialEq)]
+pub enum PollingSubscription {
+    /// `newHeads`, polled with `eth_newBlockFilter`, fetching every new block by hash
+    NewHeads,
+    /// `logs`, polled with `eth_newFilter`
+    Logs(serde_json::Value),
+    /// `newPendingTransactions`, polled with `eth_newPendingTransactionFilter`
+    NewPendingTransactions,
+    /// `alchemy_pendingTransactions`, polled with `eth_newPendingTransactionFilter`,
+    /// fetching and filtering every new transaction by hash
+    AlchemyPendingTransactions(PendingTransactionFilter),
+}
+
+/// The options of an `alchemy_pendingTransactions` subscription
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct PendingTransactionFilter {
+    /// The addresses transactions are sent to, any if empty
+    pub to: Vec<Address>,
+    /// The addresses transactions are sent from, any if empty
+    pub from: Vec<Address>,
+    /// Whether to deliver only the transaction hashes
+    pub hashes_only: bool,
+}
+
+impl PendingTransactionFilter {
+    /// Parse the options object of the subscription params
+    pub fn from_options(
+        options: Option<&serde_json::Value>,
+    ) -> Result<Self, AlchemyConnectionError> {
+        let options = match options {
+            Some(options) => options,
+            None => return Ok(Self::default()),
+        };
+        Ok(Self {
+            to: addresses(options.get("toAddress"))?,
+            from: addresses(options.get("fromAddress"))?,
+            hashes_only: options
+                .get("hashesOnly")
+                .and_then(|h| h.as_bool())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Whether every transaction matches, so none has to be fetched
+    pub fn is_empty(&self) -> bool {
+        self.to.is_empty() && self.from.is_empty()
+    }
+
+    /// Whether the transaction is sent to or from one of the addresses
+    pub fn matches(&self, transaction: &Transaction) -> bool {
+        self.is_empty()
+            || transaction.to.is_some_and(|to| self.to.contains(&to))
+            || self.from.contains(&transaction.from)
+    }
+}
+
+impl PollingSubscription {
+    /// The polled equivalent of the `eth_subscribe` params
+    pub fn from_params(params: &[serde_json::Value]) -> Result<Self, AlchemyConnectionError> {
+        match params.first().and_then(|p| p.as_str()) {
+            Some("newHeads") => Ok(PollingSubscription::NewHeads),
+            Some("logs") => Ok(PollingSubscription::Logs(
+                params
+                    .get(1)
+                    .cloned()
+                    .unwrap_or_else(|| serde_json::json!({})),
+            )),
+            Some("newPendingTransactions") => Ok(PollingSubscription::NewPendingTransactions),
+            Some("alchemy_pendingTransactions") => {
+                Ok(PollingSubscription::AlchemyPendingTransactions(
+                    PendingTransactionFilter::from_options(params.get(1))?,
+                ))
+            }
+            _ => Err(AlchemyConnectionError::UnsupportedSubscription(
+                params.first().cloned().unwrap_or_default().to_string(),
+            )),
+        }
+    }
+
+    /// Install a new filter, returning its id
+    pub async fn install(
+        &self,
+        http: &HttpConnection,
+    ) -> Result<serde_json::Value, AlchemyConnectionError> {
+        match self {
+            PollingSubscription::NewHeads => http.request("eth_newBlockFilter", ()).await,
+            PollingSubscription::Logs(filter) => http.request("eth_newFilter", [filter]).await,
+            PollingSubscription::NewPendingTransactions
+            | PollingSubscription::AlchemyPendingTransactions(_) => {
+                http.request("eth_newPendingTransactionFilter", ()).await
+            }
+        }
+    }
+
+    /// The notification result of a filter change, `None` if the change is filtered out or gone
+    async fn result(
+        &self,
+        http: &HttpConnection,
+        change: serde_json::Value,
+    ) -> Result<Option<serde_json::Value>, AlchemyConnectionError> {
+        match self {
+            PollingSubscription::Logs(_) | PollingSubscription::NewPendingTransactions => {
+                Ok(Some(change))
+            }
+            PollingSubscription::NewHeads => {
+                let block: serde_json::Value =
+                    http.request("eth_getBlockByHash", (change, false)).await?;
+                Ok(Some(block).filter(|block| !block.is_null()))
+            }
+            PollingSubscription::AlchemyPendingTransactions(filter)
+                if filter.hashes_only && filter.is_empty() =>
+            {
+                Ok(Some(change))
+            }
+            PollingSubscription::AlchemyPendingTransactions(filter) => {
+                let transaction: Option<Transaction> =
+                    http.request("eth_getTransactionByHash", [change]).await?;
+                match transaction.filter(|t| filter.matches(t)) {
+                    Some(t) if filter.hashes_only => Ok(Some(serde_json::json!(t.hash))),
+                    Some(t) => serde_json::to_value(t)
+                        .map(Some)
+                        .map_err(AlchemyConnectionError::Deserialization),
+                    None => Ok(None),
+                }
+            }
+        }
+    }
+}
+
+/// Emulate a subscription by polling a filter over http
+///
+/// The filter is installed right away, then polled with `eth_getFilterChanges` every interval by a background task.
+/// Changes are forwarded to the sender shaped like the `eth_subscription` messages of a raw connection.
+/// Filters the server no longer knows are re-installed, while other polling errors are retried on the next tick,
+/// or once the `Retry-After` of a rate limited poll has passed.
+/// The filter is uninstalled once the task is [stopped](Forwarder::stop), or once the receiving end of the sender is dropped.
+///
+/// ## Returns
+///
+/// The polling task.
+pub async fn poll_subscription(
+    http: HttpConnection,
+    params: &[serde_json::Value],
+    id: uuid::Uuid,
+    interval: Duration,
+    sender: mpsc::Sender<serde_json::Value>,
+) -> Result<Forwarder, AlchemyConnectionError> {
+    let subscription = PollingSubscription::from_params(params)?;
+    let filter = subscription.install(&http).await?;
+    Ok(Forwarder::spawn(|stop| {
+        poll(http, subscription, filter, id, interval, sender, stop)
+    }))
+}
+
+/// Polls the filter until stopped or the receiving end of the sender is dropped
+///
+/// Resolves to whether the filter was uninstalled.
+async fn poll(
+    http: HttpConnection,
+    subscription: PollingSubscription,
+    filter: serde_json::Value,
+    id: uuid::Uuid,
+    interval: Duration,
+    sender: mpsc::Sender<serde_json::Value>,
+    mut stop: oneshot::Receiver<()>,
+) -> Result<bool, AlchemyConnectionError> {
+    let name = format_uuid_simple(&id);
+    let mut filter = Some(filter);
+    let mut ticker = tokio::time::interval_at(Instant::now() + interval, interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    'polling: loop {
+        tokio::select! {
+            _ = &mut stop => break,
+            _ = sender.closed() => break,
+            _ = ticker.tick() => (),
+        }
+
+        // Re-install the filter if it expired and couldn't be re-installed right away
+        let current = match &filter {
+            Some(current) => current.clone(),
+            None => match subscription.install(&http).await {
+                Ok(installed) => filter.insert(installed).clone(),
+                Err(e) => {
+                    tracing::warn!("Failed to install the filter of {}: {:?}", name, e);
+                    continue;
+                }
+            },
+        };
+
+        let changes = match http
+            .request::<_, Vec<serde_json::Value>>("eth_getFilterChanges", [&current])
+            .await
+        {
+            Ok(changes) => changes,
+            Err(AlchemyConnectionError::RpcError(e)) if is_filter_not_found(&e) => {
+                tracing::debug!("Filter {} of {} expired: {:?}", current, name, e);
+                filter = match subscription.install(&http).await {
+                    Ok(installed) => Some(installed),
+                    Err(e) => {
+                        tracing::warn!("Failed to install the filter of {}: {:?}", name, e);
+                        None
+                    }
+                };
+                continue;
+            }
+            Err(AlchemyConnectionError::RateLimited(retry_after)) => {
+                tracing::warn!("Polling the filter of {} is rate limited", name);
+                if let Some(delay) = retry_after {
+                    ticker.reset_at(Instant::now() + delay);
+                }
+                continue;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to poll the filter of {}: {:?}", name, e);
+                continue;
+            }
+        };
+
+        for change in changes {
+            let result = match subscription.result(&http, change).await {
+                Ok(Some(result)) => result,
+                Ok(None) => continue,
+                Err(e) => {
+                    tracing::warn!("Failed to fetch a change of {}: {:?}", name, e);
+                    continue;
+                }
+            };
+            let notification = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscription",
+                "params": {
+                    "subscription": name,
+                    "result": result,
+                },
+            });
+            match sender.try_send(notification) {
+                Ok(()) => (),
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    tracing::warn!("Dropping change for full subscription buffer: {}", name)
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => break 'polling,
+            }
+        }
+    }
+
+    match filter {
+        Some(filter) => http.request("eth_uninstallFilter", [&filter]).await,
+        None => Ok(false),
+    }
+}
+
+/// Whether the json-rpc error means the filter expired or was never installed
+fn is_filter_not_found(error: &JsonRpcError) -> bool {
+    error
+        .message
+        .to_ascii_lowercase()
+        .contains("filter not found")
+}
+
+/// Parses a single address or a list of addresses
+fn addresses(value: Option<&serde_json::Value>) -> Result<Vec<Address>, AlchemyConnectionError> {
+    match value {
+        None | Some(serde_json::Value::Null) => Ok(vec![]),
+        Some(list @ serde_json::Value::Array(_)) => {
+            serde_json::from_value(list.clone()).map_err(AlchemyConnectionError::Deserialization)
+        }
+        Some(address) => serde_json::from_value(address.clone())
+            .map(|address| vec![address])
+            .map_err(AlchemyConnectionError::Deserialization),
+    }
+}
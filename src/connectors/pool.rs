@@ -0,0 +1,209 @@
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::mpsc;
+
+use super::{
+    errors::AlchemyConnectionError,
+    lock,
+    reader::{RawConnectionConfig, RawConnectionHandle},
+    registry::RouteRegistry,
+    router::SubscriptionRouter,
+};
+
+/// The default number of connections of a pool
+pub const DEFAULT_POOL_SIZE: usize = 4;
+
+/// A raw connection of a [ConnectionPool], routing the notifications of its own subscriptions
+#[derive(Debug)]
+pub struct PooledConnection {
+    /// The connection actor
+    handle: RawConnectionHandle,
+    /// Routes the connection's inbound messages, kept across reconnects
+    router: Arc<Mutex<SubscriptionRouter>>,
+}
+
+impl PooledConnection {
+    /// A clone of the connection's outbound queue
+    pub fn outbound(&self) -> mpsc::UnboundedSender<String> {
+        self.handle.outbound()
+    }
+
+    /// The router of the connection's inbound messages
+    pub fn router(&self) -> Arc<Mutex<SubscriptionRouter>> {
+        Arc::clone(&self.router)
+    }
+
+    /// Whether the connection actor gave up on the connection
+    pub fn is_closed(&self) -> bool {
+        self.handle.outbound().is_closed()
+    }
+
+    /// The number of subscriptions routed over the connection
+    pub fn subscriptions(&self) -> usize {
+        lock(&self.router).len()
+    }
+
+    /// Whether the subscription with the caller's `id` is routed over the connection
+    pub fn contains(&self, id: uuid::Uuid) -> bool {
+        lock(&self.router).contains(id)
+    }
+}
+
+/// A pool of raw connections sharing the load of many subscriptions
+///
+/// Alchemy limits the number of subscriptions per websocket, and a single socket can become the
+/// bottleneck of heavy feeds. The pool spreads subscriptions over several
+/// [RawConnectionHandle](RawConnectionHandle)s, placing each new one on the connection with the fewest
+/// subscriptions, up to an optional cap per connection.
+///
+/// Every connection routes its notifications through a [SubscriptionRouter](SubscriptionRouter) of its own,
+/// as subscription ids are only unique per connection, and is reconnected and resubscribed on its own.
+/// The notifications end up on the subscription's channel either way, so callers can't tell which connection serves it.
+#[derive(Debug)]
+pub struct ConnectionPool {
+    /// The pooled connections
+    connections: Vec<PooledConnection>,
+    /// The maximum number of subscriptions placed on one connection, unlimited if `None`
+    max_subscriptions: Option<usize>,
+}
+
+impl ConnectionPool {
+    /// Establish `size` connections with the configuration
+    pub async fn connect(
+        config: &RawConnectionConfig,
+        size: usize,
+        max_subscriptions: Option<usize>,
+    ) -> Result<Self, AlchemyConnectionError> {
+        if size == 0 {
+            return Err(AlchemyConnectionError::InvalidConfig(
+                "the pool size must not be zero".to_string(),
+            ));
+        }
+
+        let connecting = (0..size).map(|_| config.connect());
+        let connections = futures::future::try_join_all(connecting)
+            .await?
+            .into_iter()
+            .map(|connection| {
+                let router = Arc::new(Mutex::new(SubscriptionRouter::new()));
+                PooledConnection {
+                    handle: RawConnectionHandle::spawn(
+                        connection,
+                        config.clone(),
+                        Arc::clone(&router),
+                    ),
+                    router,
+                }
+            })
+            .collect();
+        Ok(Self {
+            connections,
+            max_subscriptions,
+        })
+    }
+
+    /// Replace every connection, re-issuing its subscriptions on the new one
+    ///
+    /// The subscriptions of the `registry` are rebound to the new connections, to be unsubscribed there once dropped.
+    pub async fn reconnect(
+        &mut self,
+        config: &RawConnectionConfig,
+        registry: &RouteRegistry,
+    ) -> Result<(), AlchemyConnectionError> {
+        let connecting = self.connections.iter().map(|_| config.connect());
+        let established = futures::future::try_join_all(connecting).await?;
+        for (pooled, connection) in self.connections.iter_mut().zip(established) {
+            pooled.handle =
+                RawConnectionHandle::spawn(connection, config.clone(), Arc::clone(&pooled.router));
+            registry.rebind_outbound(&pooled.router, &pooled.handle.outbound());
+        }
+        Ok(())
+    }
+
+    /// The number of connections
+    pub fn len(&self) -> usize {
+        self.connections.len()
+    }
+
+    /// Whether the pool has no connection
+    pub fn is_empty(&self) -> bool {
+        self.connections.is_empty()
+    }
+
+    /// The maximum number of subscriptions placed on one connection, unlimited if `None`
+    pub fn max_subscriptions(&self) -> Option<usize> {
+        self.max_subscriptions
+    }
+
+    /// The pooled connections
+    pub fn connections(&self) -> &[PooledConnection] {
+        &self.connections
+    }
+
+    /// The open connections with room for another subscription
+    pub fn available(&self) -> impl Iterator<Item = &PooledConnection> {
+        self.connections
+            .iter()
+            .filter(move |connection| self.check_room(connection).is_ok())
+    }
+
+    /// The number of subscriptions of every connection
+    pub fn subscription_counts(&self) -> Vec<usize> {
+        self.connections
+            .iter()
+            .map(PooledConnection::subscriptions)
+            .collect()
+    }
+
+    /// The open connection with the fewest subscriptions, eg to send requests over
+    ///
+    /// Fails with [Closed](AlchemyConnectionError::Closed) once every connection was given up on.
+    pub fn least_loaded(&self) -> Result<&PooledConnection, AlchemyConnectionError> {
+        self.connections
+            .iter()
+            .filter(|connection| !connection.is_closed())
+            .min_by_key(|connection| connection.subscriptions())
+            .ok_or(AlchemyConnectionError::Closed)
+    }
+
+    /// The connection to place a new subscription on
+    ///
+    /// Fails with a [PoolExhausted](AlchemyConnectionError::PoolExhausted) once every connection holds the maximum number of subscriptions.
+    pub fn place(&self) -> Result<&PooledConnection, AlchemyConnectionError> {
+        let connection = self.least_loaded()?;
+        self.check_room(connection)?;
+        Ok(connection)
+    }
+
+    /// The connection at `index` to place a new subscription on, eg to open one feed per connection
+    ///
+    /// Fails like [place](ConnectionPool::place) if the connection was given up on or holds the maximum number of subscriptions.
+    pub fn place_at(&self, index: usize) -> Result<&PooledConnection, AlchemyConnectionError> {
+        let connection = self
+            .connections
+            .get(index)
+            .ok_or(AlchemyConnectionError::Closed)?;
+        self.check_room(connection)?;
+        Ok(connection)
+    }
+
+    /// Fail unless the connection is open with room for another subscription
+    fn check_room(&self, connection: &PooledConnection) -> Result<(), AlchemyConnectionError> {
+        if connection.is_closed() {
+            return Err(AlchemyConnectionError::Closed);
+        }
+        match self.max_subscriptions {
+            Some(max) if connection.subscriptions() >= max => {
+                Err(AlchemyConnectionError::PoolExhausted(max))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// The connection serving the subscription with the caller's `id`
+    pub fn find(&self, id: uuid::Uuid) -> Option<&PooledConnection> {
+        self.connections
+            .iter()
+            .find(|connection| connection.contains(id))
+    }
+}
@@ -1,6 +1,10 @@
 use ethers::prelude::*;
+use futures::StreamExt;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::{mpsc, oneshot};
 
-use super::errors::AlchemyConnectionError;
+use super::{errors::AlchemyConnectionError, forwarder::Forwarder};
+use crate::messages::inbound::format_uuid_simple;
 
 /// An ethers-rs websocket [Provider](ethers::providers::Provider) for alchemy
 #[derive(Debug, Default, Clone)]
@@ -25,6 +29,120 @@ impl EthersWsProvider {
             Err(e) => Err(AlchemyConnectionError::ProviderError(e)),
         }
     }
+
+    /// Send a json-rpc request over the provider, returning its typed result
+    pub async fn request<P: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: P,
+    ) -> Result<R, AlchemyConnectionError> {
+        let provider = self.connected()?;
+        let params = match serde_json::to_value(params) {
+            // Unit params are sent as an empty list
+            Ok(serde_json::Value::Null) => serde_json::Value::Array(vec![]),
+            Ok(params) => params,
+            Err(e) => return Err(AlchemyConnectionError::Deserialization(e)),
+        };
+        let result: serde_json::Value = provider
+            .request(method, params)
+            .await
+            .map_err(AlchemyConnectionError::ProviderError)?;
+        serde_json::from_value(result).map_err(AlchemyConnectionError::Deserialization)
+    }
+
+    /// Subscribe with the given `eth_subscribe` params using ethers' pubsub machinery
+    ///
+    /// Notifications are forwarded to the sender by a background task, shaped like the
+    /// `eth_subscription` messages of a raw connection. The task unsubscribes once
+    /// [stopped](Forwarder::stop), or once the receiving end of the sender is dropped.
+    ///
+    /// ## Returns
+    ///
+    /// The subscription id and the forwarding task.
+    pub async fn subscribe(
+        &self,
+        params: Vec<serde_json::Value>,
+        sender: mpsc::Sender<serde_json::Value>,
+    ) -> Result<(uuid::Uuid, Forwarder), AlchemyConnectionError> {
+        let provider = self.connected()?.clone();
+        let (confirm, confirmation) = oneshot::channel();
+        let forwarder = Forwarder::spawn(|stop| forward(provider, params, sender, confirm, stop));
+        match confirmation.await {
+            Ok(Ok(id)) => Ok((id, forwarder)),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(AlchemyConnectionError::Closed),
+        }
+    }
+
+    /// The connected provider
+    fn connected(&self) -> Result<&Provider<Ws>, AlchemyConnectionError> {
+        self.provider
+            .as_ref()
+            .ok_or(AlchemyConnectionError::MissingConnection)
+    }
+}
+
+/// Subscribes and forwards every notification until stopped or the receiving end is dropped
+///
+/// Resolves to whether the subscription was cancelled with an `eth_unsubscribe`.
+async fn forward(
+    provider: Provider<Ws>,
+    params: Vec<serde_json::Value>,
+    sender: mpsc::Sender<serde_json::Value>,
+    confirm: oneshot::Sender<Result<uuid::Uuid, AlchemyConnectionError>>,
+    mut stop: oneshot::Receiver<()>,
+) -> Result<bool, AlchemyConnectionError> {
+    let mut stream = match provider.subscribe::<_, serde_json::Value>(params).await {
+        Ok(s) => s,
+        Err(e) => {
+            let _ = confirm.send(Err(AlchemyConnectionError::ProviderError(e)));
+            return Ok(false);
+        }
+    };
+
+    // Alchemy subscription ids are 128 bits wide
+    let id = uuid::Uuid::from_u128(stream.id.low_u128());
+    if confirm.send(Ok(id)).is_ok() {
+        let subscription = format_uuid_simple(&id);
+        loop {
+            let result = tokio::select! {
+                _ = &mut stop => break,
+                _ = sender.closed() => break,
+                result = stream.next() => result,
+            };
+            let result = match result {
+                Some(r) => r,
+                None => {
+                    tracing::debug!("Provider subscription {} ended", subscription);
+                    return Ok(false);
+                }
+            };
+
+            let notification = serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "eth_subscription",
+                "params": {
+                    "subscription": subscription,
+                    "result": result,
+                },
+            });
+            match sender.try_send(notification) {
+                Ok(()) => (),
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    tracing::warn!(
+                        "Dropping notification for full subscription buffer: {}",
+                        subscription
+                    )
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => break,
+            }
+        }
+    }
+
+    stream
+        .unsubscribe()
+        .await
+        .map_err(AlchemyConnectionError::ProviderError)
 }
 
 impl From<Provider<Ws>> for EthersWsProvider {
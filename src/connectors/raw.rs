@@ -1,9 +1,31 @@
+use std::{
+    convert::TryFrom,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_tls::TlsConnector;
 use futures::io::{BufReader, BufWriter};
-use soketto::handshake;
-use tokio::net::TcpStream;
-use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
+use soketto::{data::ByteSlice125, handshake, Incoming};
+use tokio::time::MissedTickBehavior;
+
+use super::{
+    errors::AlchemyConnectionError,
+    keepalive::{KeepaliveConfig, KeepaliveState, KEEPALIVE_PING_PAYLOAD},
+    lock,
+    stream::RawStream,
+    transport::{TcpTransport, Transport},
+    url::AlchemyUrl,
+};
+
+/// The socket type underlying the raw websocket connection
+pub type RawSocket = BufReader<BufWriter<RawStream>>;
 
-use super::errors::AlchemyConnectionError;
+/// The default maximum number of handshake redirects to follow
+pub const DEFAULT_MAX_REDIRECTS: usize = 5;
+
+/// The tick period used while waiting for frames without a keepalive
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
 
 /// A Raw, Persistent Websocket Connection to the Alchemy API using [soketto](https://docs.rs/soketto/latest/soketto/)
 ///
@@ -29,14 +51,50 @@ use super::errors::AlchemyConnectionError;
 /// < {"jsonrpc":"2.0","id":1,"result":true}
 /// ```
 ///
-#[derive(Debug, Default)]
 pub struct RawAlchemyConnection {
-    // / The websocket connection
-    // pub connection: Option<soketto::handshake::Client<'a, BufReader<BufWriter<Compat<TcpStream>>>>>,
     /// The websocket client sender after building
-    pub sender: Option<soketto::Sender<BufReader<BufWriter<Compat<TcpStream>>>>>,
+    sender: Option<soketto::Sender<RawSocket>>,
     /// The websocket client receiver after building
-    pub receiver: Option<soketto::Receiver<BufReader<BufWriter<Compat<TcpStream>>>>>,
+    receiver: Option<soketto::Receiver<RawSocket>>,
+    /// The transport opening the stream the websocket runs over
+    pub transport: Arc<dyn Transport>,
+    /// The maximum number of handshake redirects to follow
+    pub max_redirects: usize,
+    /// Whether to follow handshake redirects to another host
+    pub allow_cross_host_redirects: bool,
+    /// The keepalive configuration, disabling pings if `None`
+    pub keepalive: Option<KeepaliveConfig>,
+    /// Additional headers sent with the handshake request
+    pub headers: Vec<(String, String)>,
+    /// The maximum size of an inbound message, using soketto's default if `None`
+    pub max_message_size: Option<usize>,
+    /// The keepalive bookkeeping of the current connection
+    keepalive_state: KeepaliveState,
+}
+
+impl Default for RawAlchemyConnection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for RawAlchemyConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RawAlchemyConnection")
+            .field("sender", &self.sender)
+            .field("receiver", &self.receiver)
+            .field("transport", &self.transport)
+            .field("max_redirects", &self.max_redirects)
+            .field(
+                "allow_cross_host_redirects",
+                &self.allow_cross_host_redirects,
+            )
+            .field("keepalive", &self.keepalive)
+            .field("headers", &self.headers)
+            .field("max_message_size", &self.max_message_size)
+            .field("keepalive_state", &self.keepalive_state)
+            .finish()
+    }
 }
 
 impl RawAlchemyConnection {
@@ -45,37 +103,407 @@ impl RawAlchemyConnection {
         Self {
             sender: None,
             receiver: None,
+            transport: Arc::new(TcpTransport::new()),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            allow_cross_host_redirects: false,
+            keepalive: Some(KeepaliveConfig::default()),
+            headers: vec![],
+            max_message_size: None,
+            keepalive_state: KeepaliveState::new(),
         }
     }
 
+    /// Set the keepalive configuration, disabling pings if `None`
+    pub fn with_keepalive(mut self, keepalive: Option<KeepaliveConfig>) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    /// The round-trip time of the last answered keepalive ping
+    pub fn last_rtt(&self) -> Option<Duration> {
+        self.keepalive_state.last_rtt
+    }
+
+    /// Whether the connection was established
+    pub fn is_connected(&self) -> bool {
+        self.sender.is_some() && self.receiver.is_some()
+    }
+
+    /// Use a custom [TlsConnector](async_tls::TlsConnector) for the tls handshake
+    ///
+    /// This is useful for trusting self-signed certificates, for example in a local test server.
+    pub fn with_tls_connector(mut self, connector: TlsConnector) -> Self {
+        self.transport = Arc::new(TcpTransport::new().with_tls_connector(connector));
+        self
+    }
+
+    /// Set the [Transport](Transport) opening the stream the websocket runs over, eg a unix socket or an in-memory pipe
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Set the maximum number of handshake redirects to follow
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    /// Set whether to follow handshake redirects to another host
+    ///
+    /// The api key is part of the url, so only allow this for hosts trusted with the key.
+    pub fn with_cross_host_redirects(mut self, allow: bool) -> Self {
+        self.allow_cross_host_redirects = allow;
+        self
+    }
+
+    /// Set additional headers to send with the handshake request
+    pub fn with_headers(mut self, headers: Vec<(String, String)>) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Set the maximum size of an inbound message
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = Some(max_message_size);
+        self
+    }
+
     /// Connect to the sokettot websocket
+    ///
+    /// Both plain `ws://` urls (eg a local node) and tls-encrypted `wss://` urls (eg the Alchemy API) are supported.
+    /// The stream is opened by the connection's [transport](RawAlchemyConnection::transport), tcp by default.
+    ///
+    /// Handshake redirects are followed up to [max_redirects](RawAlchemyConnection::max_redirects) times,
+    /// as long as they neither downgrade `wss` to `ws` nor, unless [allowed](RawAlchemyConnection::with_cross_host_redirects),
+    /// lead to another host. Rejections are mapped to their [AlchemyConnectionError](AlchemyConnectionError) variant.
     pub async fn connect(&mut self, url: &'_ str) -> Result<(), AlchemyConnectionError> {
-        // Create the socket connection
-        let socket = match tokio::net::TcpStream::connect(url).await {
-            Ok(s) => s,
-            Err(e) => return Err(AlchemyConnectionError::RawStreamError(e)),
+        // Parse the url into its components
+        let mut url = AlchemyUrl::parse(url)?;
+        let mut redirects = 0;
+
+        loop {
+            // Open the (optionally encrypted) stream
+            let stream = self.transport.open(&url).await?;
+
+            // Create the client connection
+            let host = url.host_header();
+            let resource = url.resource();
+            let compatible_socket = BufReader::new(BufWriter::new(stream));
+            let headers: Vec<handshake::client::Header> = self
+                .headers
+                .iter()
+                .map(|(name, value)| handshake::client::Header {
+                    name,
+                    value: value.as_bytes(),
+                })
+                .collect();
+            let mut client = handshake::Client::new(compatible_socket, &host, &resource);
+            client.set_headers(&headers);
+
+            // Handshake the connection
+            let response = client.handshake().await;
+            match response {
+                Ok(handshake::ServerResponse::Accepted { protocol }) => {
+                    tracing::info!(
+                        "Handshake accepted by {} with protocol {:?}",
+                        url.redacted(),
+                        protocol
+                    );
+                    let mut builder = client.into_builder();
+                    if let Some(max) = self.max_message_size {
+                        builder.set_max_message_size(max);
+                        builder.set_max_frame_size(max);
+                    }
+                    let (sender, receiver) = builder.finish();
+                    self.sender = Some(sender);
+                    self.receiver = Some(receiver);
+                    self.keepalive_state = KeepaliveState::new();
+                    return Ok(());
+                }
+                Ok(handshake::ServerResponse::Redirect {
+                    status_code,
+                    location,
+                }) => {
+                    if redirects >= self.max_redirects {
+                        return Err(AlchemyConnectionError::TooManyRedirects(redirects));
+                    }
+                    redirects += 1;
+                    url = url.redirect(&location, self.allow_cross_host_redirects)?;
+                    tracing::info!(
+                        "Following {} handshake redirect to {}",
+                        status_code,
+                        url.redacted()
+                    );
+                }
+                Ok(handshake::ServerResponse::Rejected { status_code }) => {
+                    tracing::warn!(
+                        "Handshake rejected by {} with status {}",
+                        url.redacted(),
+                        status_code
+                    );
+                    return Err(AlchemyConnectionError::from_rejection(status_code));
+                }
+                Err(e) => return Err(AlchemyConnectionError::RawHandshakeError(e)),
+            }
+        }
+    }
+
+    /// Receive the next text message from the websocket
+    ///
+    /// While waiting, keepalive pings are sent every [ping_interval](KeepaliveConfig::ping_interval).
+    /// The connection is declared dead with a [KeepaliveTimeout](AlchemyConnectionError::KeepaliveTimeout)
+    /// after too many missed pongs, with an [IdleTimeout](AlchemyConnectionError::IdleTimeout) after too long a silence,
+    /// or with a [WriteTimeout](AlchemyConnectionError::WriteTimeout) if a ping can't be written.
+    pub async fn receive_text(&mut self) -> Result<String, AlchemyConnectionError> {
+        let Self {
+            sender,
+            receiver,
+            keepalive,
+            keepalive_state,
+            ..
+        } = self;
+
+        // Extract the receiver from the contained websocket channel
+        let receiver = match receiver {
+            Some(r) => r,
+            None => return Err(AlchemyConnectionError::MissingReceiver),
+        };
+
+        loop {
+            let mut data = vec![];
+
+            // Wait for the next frame, keeping the connection alive in the meantime.
+            // The receive future is never dropped before completion so no partial frame is lost.
+            let frame = {
+                let recv = receiver.receive(&mut data);
+                tokio::pin!(recv);
+                let period = keepalive
+                    .as_ref()
+                    .map(|k| k.check_interval())
+                    .unwrap_or(IDLE_CHECK_INTERVAL)
+                    .max(Duration::from_millis(1));
+                let mut ticker = tokio::time::interval(period);
+                ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    tokio::select! {
+                        res = &mut recv => break res.map(Frame::from),
+                        _ = ticker.tick(), if keepalive.is_some() => {
+                            if let Some(config) = keepalive.as_ref() {
+                                if keepalive_state.check(config)? {
+                                    let sender = sender
+                                        .as_mut()
+                                        .ok_or(AlchemyConnectionError::MissingSender)?;
+                                    send_ping(sender, config.write_timeout).await?;
+                                    keepalive_state.on_ping();
+                                }
+                            }
+                        }
+                    }
+                }
+            };
+
+            if let Some(text) = read_frame(frame, data, keepalive_state)? {
+                return Ok(text);
+            }
+        }
+    }
+
+    /// Send a text message over the websocket
+    pub async fn send_text(&mut self, text: String) -> Result<(), AlchemyConnectionError> {
+        match self.sender.as_mut() {
+            Some(sender) => send_text(sender, text).await,
+            None => Err(AlchemyConnectionError::MissingSender),
+        }
+    }
+
+    /// Split the connection into its writing and reading halves, eg to drive them from separate tasks
+    ///
+    /// The halves share the keepalive bookkeeping:
+    /// the reader records inbound frames and pongs, while the writer sends the pings and declares the connection dead.
+    pub fn split(
+        self,
+    ) -> Result<(RawConnectionWriter, RawConnectionReader), AlchemyConnectionError> {
+        let sender = self.sender.ok_or(AlchemyConnectionError::MissingSender)?;
+        let receiver = self
+            .receiver
+            .ok_or(AlchemyConnectionError::MissingReceiver)?;
+        let keepalive_state = Arc::new(Mutex::new(self.keepalive_state));
+        let writer = RawConnectionWriter {
+            sender,
+            keepalive: self.keepalive,
+            keepalive_state: Arc::clone(&keepalive_state),
+        };
+        let reader = RawConnectionReader {
+            receiver,
+            keepalive_state,
         };
+        Ok((writer, reader))
+    }
 
-        // Create the client connection
-        let compatible_socket = BufReader::new(BufWriter::new(socket.compat()));
-        let mut client = handshake::Client::new(compatible_socket, url, "");
-
-        // Handshake the connection
-        match client.handshake().await {
-            Ok(sr) => {
-                tracing::info!("Got handshake response: {:?}", sr);
-                tracing::debug!(
-                    "Expecting server response: {:?}",
-                    handshake::ServerResponse::Accepted { protocol: None }
-                );
+    /// Close the websocket connection
+    pub async fn close(&mut self) {
+        if let Some(sender) = self.sender.as_mut() {
+            if let Err(e) = sender.close().await {
+                tracing::debug!("Failed to close the websocket: {:?}", e);
             }
-            Err(e) => return Err(AlchemyConnectionError::RawHandshakeError(e)),
         }
+    }
+}
 
-        let (sender, receiver) = client.into_builder().finish();
-        self.sender = Some(sender);
-        self.receiver = Some(receiver);
+/// The writing half of a split [RawAlchemyConnection](RawAlchemyConnection)
+#[derive(Debug)]
+pub struct RawConnectionWriter {
+    /// The websocket client sender
+    sender: soketto::Sender<RawSocket>,
+    /// The keepalive configuration, disabling pings if `None`
+    keepalive: Option<KeepaliveConfig>,
+    /// The keepalive bookkeeping shared with the reader
+    keepalive_state: Arc<Mutex<KeepaliveState>>,
+}
+
+impl RawConnectionWriter {
+    /// Send a text message over the websocket
+    pub async fn send_text(&mut self, text: String) -> Result<(), AlchemyConnectionError> {
+        send_text(&mut self.sender, text).await
+    }
 
+    /// The period [check_keepalive](RawConnectionWriter::check_keepalive) should be called with,
+    /// `None` if the keepalive is disabled
+    pub fn keepalive_interval(&self) -> Option<Duration> {
+        self.keepalive
+            .as_ref()
+            .map(|k| k.check_interval().max(Duration::from_millis(1)))
+    }
+
+    /// Send a keepalive ping if one is due
+    ///
+    /// Fails with a [KeepaliveTimeout](AlchemyConnectionError::KeepaliveTimeout) after too many missed pongs,
+    /// with an [IdleTimeout](AlchemyConnectionError::IdleTimeout) after too long a silence,
+    /// or with a [WriteTimeout](AlchemyConnectionError::WriteTimeout) if the ping can't be written.
+    pub async fn check_keepalive(&mut self) -> Result<(), AlchemyConnectionError> {
+        let config = match self.keepalive.as_ref() {
+            Some(config) => config,
+            None => return Ok(()),
+        };
+        let due = lock(&self.keepalive_state).check(config)?;
+        if due {
+            send_ping(&mut self.sender, config.write_timeout).await?;
+            lock(&self.keepalive_state).on_ping();
+        }
         Ok(())
     }
+
+    /// The round-trip time of the last answered keepalive ping
+    pub fn last_rtt(&self) -> Option<Duration> {
+        lock(&self.keepalive_state).last_rtt
+    }
+
+    /// Close the websocket connection
+    pub async fn close(&mut self) {
+        if let Err(e) = self.sender.close().await {
+            tracing::debug!("Failed to close the websocket: {:?}", e);
+        }
+    }
+}
+
+/// The reading half of a split [RawAlchemyConnection](RawAlchemyConnection)
+#[derive(Debug)]
+pub struct RawConnectionReader {
+    /// The websocket client receiver
+    receiver: soketto::Receiver<RawSocket>,
+    /// The keepalive bookkeeping shared with the writer
+    keepalive_state: Arc<Mutex<KeepaliveState>>,
+}
+
+impl RawConnectionReader {
+    /// Receive the next text message from the websocket, recording pongs for the writer's keepalive
+    pub async fn receive_text(&mut self) -> Result<String, AlchemyConnectionError> {
+        loop {
+            let mut data = vec![];
+            let frame = self.receiver.receive(&mut data).await.map(Frame::from);
+            if let Some(text) = read_frame(frame, data, &mut lock(&self.keepalive_state))? {
+                return Ok(text);
+            }
+        }
+    }
+}
+
+/// A received websocket frame, detached from the receiver's buffers
+enum Frame {
+    Text,
+    Binary,
+    Pong,
+    Closed,
+}
+
+impl From<Incoming<'_>> for Frame {
+    fn from(incoming: Incoming<'_>) -> Self {
+        match incoming {
+            Incoming::Data(soketto::Data::Text(_)) => Frame::Text,
+            Incoming::Data(soketto::Data::Binary(_)) => Frame::Binary,
+            Incoming::Pong(_) => Frame::Pong,
+            Incoming::Closed(_) => Frame::Closed,
+        }
+    }
+}
+
+/// Records a received frame in the keepalive state, returning its text if it carried any
+fn read_frame(
+    frame: Result<Frame, soketto::connection::Error>,
+    data: Vec<u8>,
+    keepalive_state: &mut KeepaliveState,
+) -> Result<Option<String>, AlchemyConnectionError> {
+    match frame {
+        Ok(Frame::Text) => {
+            keepalive_state.on_frame();
+            String::from_utf8(data)
+                .map(Some)
+                .map_err(|_| AlchemyConnectionError::InvalidTextString)
+        }
+        Ok(Frame::Binary) => {
+            keepalive_state.on_frame();
+            Err(AlchemyConnectionError::UnexpectedResponseType)
+        }
+        Ok(Frame::Pong) => {
+            keepalive_state.on_pong();
+            Ok(None)
+        }
+        Ok(Frame::Closed) | Err(soketto::connection::Error::Closed) => {
+            Err(AlchemyConnectionError::Closed)
+        }
+        Err(e) => Err(AlchemyConnectionError::SomeError(e)),
+    }
+}
+
+/// Sends a text message
+async fn send_text(
+    sender: &mut soketto::Sender<RawSocket>,
+    text: String,
+) -> Result<(), AlchemyConnectionError> {
+    if let Err(e) = sender.send_text(text).await {
+        return Err(AlchemyConnectionError::SendFailed(e));
+    }
+    if let Err(e) = sender.flush().await {
+        return Err(AlchemyConnectionError::FlushFailed(e));
+    }
+    Ok(())
+}
+
+/// Sends a keepalive ping, declaring the connection dead if the ping can't be written in time
+async fn send_ping(
+    sender: &mut soketto::Sender<RawSocket>,
+    timeout: Duration,
+) -> Result<(), AlchemyConnectionError> {
+    let payload = ByteSlice125::try_from(KEEPALIVE_PING_PAYLOAD)
+        .expect("the keepalive ping payload is shorter than 125 bytes");
+    let ping = async {
+        sender.send_ping(payload).await?;
+        sender.flush().await
+    };
+    match tokio::time::timeout(timeout, ping).await {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(e)) => Err(AlchemyConnectionError::SendFailed(e)),
+        Err(_) => Err(AlchemyConnectionError::WriteTimeout(timeout)),
+    }
 }
@@ -0,0 +1,469 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::{Interval, MissedTickBehavior},
+};
+
+use super::{
+    errors::AlchemyConnectionError,
+    keepalive::KeepaliveConfig,
+    lock,
+    pending::{RequestIdAllocator, DEFAULT_REQUEST_TIMEOUT},
+    raw::{RawAlchemyConnection, RawConnectionReader, RawConnectionWriter},
+    reconnect::ReconnectPolicy,
+    router::SubscriptionRouter,
+    transport::{TcpTransport, Transport},
+    url::redact_url,
+};
+use crate::messages::{
+    inbound::subscription_id,
+    outbound::{AlchemySocketMessage, OutSocketMethod},
+};
+
+/// The configuration a raw connection is (re)established with
+#[derive(Debug, Clone)]
+pub struct RawConnectionConfig {
+    /// The websocket url
+    pub url: String,
+    /// The keepalive configuration, disabling pings if `None`
+    pub keepalive: Option<KeepaliveConfig>,
+    /// The policy used to reconnect after the connection drops
+    pub reconnect_policy: ReconnectPolicy,
+    /// Additional headers sent with the handshake request
+    pub headers: Vec<(String, String)>,
+    /// The time allowed to open the connection and complete the handshake, unlimited if `None`
+    pub connect_timeout: Option<Duration>,
+    /// The maximum size of an inbound message, using soketto's default if `None`
+    pub max_message_size: Option<usize>,
+    /// The transport opening the stream the websocket runs over
+    pub transport: Arc<dyn Transport>,
+    /// The time to wait for the confirmation of a subscription re-issued after a reconnect
+    pub request_timeout: Duration,
+    /// Allocates the ids of the re-issued subscriptions, shared with the clients of the connection
+    pub request_ids: Arc<RequestIdAllocator>,
+}
+
+impl RawConnectionConfig {
+    /// Create a new configuration for the url with default settings
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_string(),
+            keepalive: Some(KeepaliveConfig::default()),
+            reconnect_policy: ReconnectPolicy::default(),
+            headers: vec![],
+            connect_timeout: None,
+            max_message_size: None,
+            transport: Arc::new(TcpTransport::new()),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            request_ids: Arc::new(RequestIdAllocator::new()),
+        }
+    }
+
+    /// Establish a new connection
+    pub async fn connect(&self) -> Result<RawAlchemyConnection, AlchemyConnectionError> {
+        let mut connection = RawAlchemyConnection::new()
+            .with_keepalive(self.keepalive.clone())
+            .with_headers(self.headers.clone())
+            .with_transport(Arc::clone(&self.transport));
+        if let Some(max) = self.max_message_size {
+            connection = connection.with_max_message_size(max);
+        }
+
+        match self.connect_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, connection.connect(&self.url))
+                .await
+                .map_err(|_| AlchemyConnectionError::ConnectTimeout(timeout))??,
+            None => connection.connect(&self.url).await?,
+        }
+        Ok(connection)
+    }
+}
+
+/// A handle to a [RawAlchemyConnection](RawAlchemyConnection) owned by a background connection actor
+///
+/// The actor splits the connection into two concurrent tasks:
+/// a writer task sending the messages queued with [send](RawConnectionHandle::send) along with the keepalive pings,
+/// and a reader task dispatching every inbound message through the shared [SubscriptionRouter](SubscriptionRouter).
+/// Sends therefore never wait on a pending receive.
+///
+/// When the connection drops, the task reconnects according to its [ReconnectPolicy](ReconnectPolicy)
+/// and re-issues every routed subscription, so subscriptions survive reconnects without the caller noticing.
+/// Once it gives up, every route is removed, ending all subscriptions.
+///
+/// Dropping the handle closes the connection, leaving the routes to whichever connection takes over the router.
+/// Clients holding a clone of the outbound queue don't keep the connection open.
+#[derive(Debug)]
+pub struct RawConnectionHandle {
+    /// The queue of outbound messages
+    outbound: mpsc::UnboundedSender<String>,
+    /// Tells the actor to close the connection, fired on drop
+    shutdown: Option<oneshot::Sender<()>>,
+    /// The actor task, resolving to the reason the connection ended
+    task: Option<JoinHandle<AlchemyConnectionError>>,
+}
+
+impl RawConnectionHandle {
+    /// Spawn the connection actor for an established connection
+    ///
+    /// Subscriptions already routed (eg by a previous connection) are re-issued first.
+    pub fn spawn(
+        connection: RawAlchemyConnection,
+        config: RawConnectionConfig,
+        router: Arc<Mutex<SubscriptionRouter>>,
+    ) -> Self {
+        let (outbound, outbound_rx) = mpsc::unbounded_channel();
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let task = tokio::spawn(supervise(
+            connection,
+            config,
+            router,
+            outbound_rx,
+            shutdown_rx,
+        ));
+        Self {
+            outbound,
+            shutdown: Some(shutdown),
+            task: Some(task),
+        }
+    }
+
+    /// Queue a text message to be sent over the websocket
+    pub fn send(&self, text: String) -> Result<(), AlchemyConnectionError> {
+        self.outbound
+            .send(text)
+            .map_err(|_| AlchemyConnectionError::Closed)
+    }
+
+    /// A clone of the outbound queue, eg for subscription handles
+    pub fn outbound(&self) -> mpsc::UnboundedSender<String> {
+        self.outbound.clone()
+    }
+
+    /// Wait for the connection actor to give up on the connection, returning the reason
+    ///
+    /// Resolves immediately with [Closed](AlchemyConnectionError::Closed) once the reason was taken.
+    pub async fn closed(&mut self) -> AlchemyConnectionError {
+        let reason = match self.task.as_mut() {
+            Some(task) => match task.await {
+                Ok(reason) => reason,
+                Err(e) => {
+                    tracing::warn!("Connection actor failed: {:?}", e);
+                    AlchemyConnectionError::Closed
+                }
+            },
+            None => return AlchemyConnectionError::Closed,
+        };
+        self.task = None;
+        reason
+    }
+}
+
+impl Drop for RawConnectionHandle {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Runs the connection, reconnecting and resubscribing whenever it drops
+async fn supervise(
+    mut connection: RawAlchemyConnection,
+    config: RawConnectionConfig,
+    router: Arc<Mutex<SubscriptionRouter>>,
+    mut outbound_rx: mpsc::UnboundedReceiver<String>,
+    mut shutdown: oneshot::Receiver<()>,
+) -> AlchemyConnectionError {
+    let reason = loop {
+        let (reason, rx) = run(connection, &config, &router, outbound_rx, &mut shutdown).await;
+
+        // The handle was dropped, shut down without touching the routes
+        outbound_rx = match rx {
+            Some(rx) => rx,
+            None => return AlchemyConnectionError::Closed,
+        };
+
+        if !(reason.is_disconnect() && config.reconnect_policy.is_enabled()) {
+            break reason;
+        }
+        tracing::warn!("Connection lost: {:?}", reason);
+
+        match reconnect(&config, &mut shutdown).await {
+            Ok(Some(c)) => connection = c,
+            Ok(None) => return AlchemyConnectionError::Closed,
+            Err(e) => break e,
+        }
+    };
+
+    // Close the queue first, so no subscription is routed once the routes are cleared
+    tracing::warn!("Giving up on the connection: {:?}", reason);
+    drop(outbound_rx);
+    lock(&router).clear();
+    reason
+}
+
+/// Why the actor stops the writer task
+enum WriterStop {
+    /// The connection failed, hand back the outbound queue for the next connection
+    Reconnect,
+    /// The handle was dropped, close the websocket
+    Close,
+}
+
+/// How the writer task ended
+enum WriterExit {
+    /// The websocket was closed after the handle was dropped
+    Shutdown,
+    /// The actor stopped the task, handing back the outbound queue
+    Stopped(mpsc::UnboundedReceiver<String>),
+    /// Writing to the websocket failed
+    Failed(mpsc::UnboundedReceiver<String>, AlchemyConnectionError),
+}
+
+/// Runs the writer and reader tasks of a connection until either of them fails, resubscribing in the meantime
+///
+/// Returns the reason along with the outbound queue, so queued messages survive a reconnect,
+/// or `None` in place of the queue once the handle is dropped.
+async fn run(
+    connection: RawAlchemyConnection,
+    config: &RawConnectionConfig,
+    router: &Arc<Mutex<SubscriptionRouter>>,
+    outbound_rx: mpsc::UnboundedReceiver<String>,
+    shutdown: &mut oneshot::Receiver<()>,
+) -> (
+    AlchemyConnectionError,
+    Option<mpsc::UnboundedReceiver<String>>,
+) {
+    let (writer, reader) = match connection.split() {
+        Ok(halves) => halves,
+        Err(e) => return (e, Some(outbound_rx)),
+    };
+    let (control, control_rx) = mpsc::unbounded_channel();
+    let (stop, stop_rx) = oneshot::channel();
+    let mut writer_task = tokio::spawn(write(writer, outbound_rx, control_rx, stop_rx));
+    let mut reader_task = tokio::spawn(read(reader, Arc::clone(router)));
+
+    let resubscribing = resubscribe(&control, router, config);
+    tokio::pin!(resubscribing);
+    let mut resubscribed = false;
+
+    let reason = loop {
+        tokio::select! {
+            _ = &mut *shutdown => {
+                tracing::debug!("Connection handle dropped, closing the websocket");
+                reader_task.abort();
+                let _ = stop.send(WriterStop::Close);
+                if let Err(e) = writer_task.await {
+                    tracing::warn!("Writer task failed: {:?}", e);
+                }
+                return (AlchemyConnectionError::Closed, None);
+            }
+            res = &mut resubscribing, if !resubscribed => match res {
+                Ok(()) => resubscribed = true,
+                Err(e) => break e,
+            },
+            res = &mut writer_task => {
+                reader_task.abort();
+                return match res {
+                    Ok(WriterExit::Shutdown) => (AlchemyConnectionError::Closed, None),
+                    Ok(WriterExit::Stopped(rx)) => (AlchemyConnectionError::Closed, Some(rx)),
+                    Ok(WriterExit::Failed(rx, e)) => (e, Some(rx)),
+                    Err(e) => {
+                        tracing::warn!("Writer task failed: {:?}", e);
+                        (AlchemyConnectionError::Closed, None)
+                    }
+                };
+            }
+            res = &mut reader_task => break match res {
+                Ok(reason) => reason,
+                Err(e) => {
+                    tracing::warn!("Reader task failed: {:?}", e);
+                    AlchemyConnectionError::Closed
+                }
+            },
+        }
+    };
+
+    // Stop the writer gracefully to take back the outbound queue
+    reader_task.abort();
+    let _ = stop.send(WriterStop::Reconnect);
+    match writer_task.await {
+        Ok(WriterExit::Shutdown) => (AlchemyConnectionError::Closed, None),
+        Ok(WriterExit::Stopped(rx)) | Ok(WriterExit::Failed(rx, _)) => (reason, Some(rx)),
+        Err(e) => {
+            tracing::warn!("Writer task failed: {:?}", e);
+            (AlchemyConnectionError::Closed, None)
+        }
+    }
+}
+
+/// Writes resubscriptions, queued outbound messages and keepalive pings to the websocket until stopped
+///
+/// Closes the websocket when stopped because the handle was dropped.
+async fn write(
+    mut writer: RawConnectionWriter,
+    mut outbound_rx: mpsc::UnboundedReceiver<String>,
+    mut control_rx: mpsc::UnboundedReceiver<String>,
+    mut stop: oneshot::Receiver<WriterStop>,
+) -> WriterExit {
+    let mut ticker = writer.keepalive_interval().map(|period| {
+        let mut ticker = tokio::time::interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    });
+
+    loop {
+        let result = tokio::select! {
+            biased;
+            reason = &mut stop => match reason {
+                Ok(WriterStop::Reconnect) => return WriterExit::Stopped(outbound_rx),
+                Ok(WriterStop::Close) | Err(_) => {
+                    writer.close().await;
+                    return WriterExit::Shutdown;
+                }
+            },
+            Some(text) = control_rx.recv() => writer.send_text(text).await,
+            Some(text) = outbound_rx.recv() => writer.send_text(text).await,
+            _ = tick(&mut ticker) => writer.check_keepalive().await,
+        };
+        if let Err(e) = result {
+            return WriterExit::Failed(outbound_rx, e);
+        }
+    }
+}
+
+/// Dispatches inbound messages until the connection fails
+///
+/// Binary frames and text frames that aren't valid utf-8 are skipped, a stray frame doesn't break the connection.
+async fn read(
+    mut reader: RawConnectionReader,
+    router: Arc<Mutex<SubscriptionRouter>>,
+) -> AlchemyConnectionError {
+    loop {
+        match reader.receive_text().await {
+            Ok(text) => dispatch(&router, &text),
+            Err(
+                e @ (AlchemyConnectionError::UnexpectedResponseType
+                | AlchemyConnectionError::InvalidTextString),
+            ) => tracing::warn!("Skipping unreadable frame: {:?}", e),
+            Err(e) => return e,
+        }
+    }
+}
+
+/// Waits for the next keepalive tick, never resolving without a keepalive
+async fn tick(ticker: &mut Option<Interval>) {
+    match ticker {
+        Some(ticker) => {
+            ticker.tick().await;
+        }
+        None => futures::future::pending().await,
+    }
+}
+
+/// Establishes a new connection with exponential backoff
+///
+/// Resolves to `None` once the handle is dropped.
+async fn reconnect(
+    config: &RawConnectionConfig,
+    shutdown: &mut oneshot::Receiver<()>,
+) -> Result<Option<RawAlchemyConnection>, AlchemyConnectionError> {
+    let policy = &config.reconnect_policy;
+    let mut attempt = 0;
+    while policy.allows(attempt) {
+        let delay = policy.backoff_with_jitter(attempt);
+        attempt += 1;
+        tracing::info!(
+            "Reconnecting to {} in {:?} (attempt {})",
+            redact_url(&config.url),
+            delay,
+            attempt
+        );
+        let connecting = async {
+            tokio::time::sleep(delay).await;
+            config.connect().await
+        };
+
+        tokio::select! {
+            _ = &mut *shutdown => return Ok(None),
+            res = connecting => match res {
+                Ok(connection) => return Ok(Some(connection)),
+                Err(e) => tracing::warn!("Reconnection attempt {} failed: {:?}", attempt, e),
+            },
+        }
+    }
+    Err(AlchemyConnectionError::ReconnectFailed(attempt))
+}
+
+/// Re-issues every routed subscription through the writer task, rerouting it to the new subscription id
+///
+/// Every `eth_subscribe` gets a fresh request id and is sent right away, the responses are then delivered
+/// by the reader task through the router's pending requests. Subscriptions the server rejects, or doesn't
+/// confirm within the [request timeout](RawConnectionConfig::request_timeout), are dropped, ending them.
+async fn resubscribe(
+    control: &mpsc::UnboundedSender<String>,
+    router: &Mutex<SubscriptionRouter>,
+    config: &RawConnectionConfig,
+) -> Result<(), AlchemyConnectionError> {
+    let subscriptions = lock(router).subscriptions();
+
+    let mut confirmations = Vec::with_capacity(subscriptions.len());
+    for (server_id, params) in subscriptions {
+        let id = config.request_ids.next_id();
+        let message = AlchemySocketMessage {
+            id,
+            method: OutSocketMethod::Subscribe,
+            params,
+        };
+        let text =
+            serde_json::to_string(&message).map_err(AlchemyConnectionError::Deserialization)?;
+
+        // Register before sending so the response can't slip past us
+        let response = lock(router).wait(id);
+        control
+            .send(text)
+            .map_err(|_| AlchemyConnectionError::Closed)?;
+        let timeout = config.request_timeout;
+        confirmations
+            .push(async move { (server_id, id, tokio::time::timeout(timeout, response).await) });
+    }
+
+    let mut moves = vec![];
+    let confirmations = futures::future::join_all(confirmations).await;
+    let mut router = lock(router);
+    for (server_id, id, response) in confirmations {
+        let response = match response {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => return Err(AlchemyConnectionError::Closed),
+            Err(_) => {
+                tracing::warn!("Resubscribing {} timed out, dropping it", server_id);
+                router.cancel(id);
+                moves.push((server_id, None));
+                continue;
+            }
+        };
+        match subscription_id(response) {
+            Ok(new_server_id) => {
+                tracing::debug!("Resubscribed {} as {}", server_id, new_server_id);
+                moves.push((server_id, Some(new_server_id)));
+            }
+            Err(e) => {
+                tracing::warn!("Failed to resubscribe {}, dropping it: {:?}", server_id, e);
+                moves.push((server_id, None));
+            }
+        }
+    }
+    router.reroute_all(moves);
+
+    Ok(())
+}
+
+/// Dispatch an inbound message without holding the router lock across an await
+pub(crate) fn dispatch(router: &Mutex<SubscriptionRouter>, text: &str) {
+    lock(router).dispatch(text);
+}
@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// A reconnection policy using exponential backoff with jitter
+///
+/// The delay before reconnection attempt `n` (starting at zero) is
+/// `initial_backoff * multiplier^n`, capped at `max_backoff`, and then
+/// randomly spread by up to `jitter` (a fraction of the delay) in either direction.
+///
+/// ## Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use alchemy_rs::connectors::prelude::*;
+///
+/// let policy = ReconnectPolicy {
+///     max_retries: Some(5),
+///     initial_backoff: Duration::from_millis(100),
+///     ..Default::default()
+/// };
+/// assert_eq!(policy.backoff(0), Duration::from_millis(100));
+/// assert_eq!(policy.backoff(3), Duration::from_millis(800));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    /// The maximum number of reconnection attempts, retrying forever if `None`
+    pub max_retries: Option<usize>,
+    /// The delay before the first reconnection attempt
+    pub initial_backoff: Duration,
+    /// The upper bound on the delay between reconnection attempts
+    pub max_backoff: Duration,
+    /// The factor the delay grows by after each failed attempt
+    pub multiplier: f64,
+    /// The fraction of the delay to randomly add or subtract, between `0.0` and `1.0`
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: Some(10),
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// A policy that never reconnects
+    pub fn disabled() -> Self {
+        Self {
+            max_retries: Some(0),
+            ..Default::default()
+        }
+    }
+
+    /// Whether any reconnection attempt is allowed
+    pub fn is_enabled(&self) -> bool {
+        self.max_retries != Some(0)
+    }
+
+    /// Whether the given (zero-indexed) attempt is allowed
+    pub fn allows(&self, attempt: usize) -> bool {
+        match self.max_retries {
+            Some(max) => attempt < max,
+            None => true,
+        }
+    }
+
+    /// The delay before the given (zero-indexed) attempt, without jitter
+    pub fn backoff(&self, attempt: usize) -> Duration {
+        let exponent = attempt.min(i32::MAX as usize) as i32;
+        let delay = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        if !delay.is_finite() || delay >= self.max_backoff.as_secs_f64() {
+            return self.max_backoff;
+        }
+        Duration::from_secs_f64(delay)
+    }
+
+    /// The delay before the given (zero-indexed) attempt, randomly spread by the jitter
+    pub fn backoff_with_jitter(&self, attempt: usize) -> Duration {
+        let delay = self.backoff(attempt).as_secs_f64();
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return Duration::from_secs_f64(delay);
+        }
+        let factor = rand::thread_rng().gen_range((1.0 - jitter)..=(1.0 + jitter));
+        Duration::from_secs_f64(delay * factor)
+    }
+}
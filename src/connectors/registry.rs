@@ -0,0 +1,84 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::mpsc;
+
+use super::{lock, router::SubscriptionRouter};
+use crate::messages::inbound::parse_uuid_simple;
+
+/// The connection a subscription is currently routed by
+#[derive(Debug, Clone)]
+pub struct RouteBinding {
+    /// The connection's router
+    pub router: Arc<Mutex<SubscriptionRouter>>,
+    /// The connection's outbound queue, used to unsubscribe
+    pub outbound: mpsc::UnboundedSender<String>,
+}
+
+/// The connections subscriptions are routed by, keyed by the subscription id handed to the caller
+///
+/// A registry is shared by every connection of a manager, so subscription ids are unique across its
+/// connections, and a subscription moved to another connection (eg by a failover) is released on the
+/// connection it ended up on once its [SubscriptionStream](crate::subscription::SubscriptionStream) is dropped.
+#[derive(Debug, Default)]
+pub struct RouteRegistry {
+    /// The binding of every routed subscription
+    bindings: Mutex<HashMap<uuid::Uuid, RouteBinding>>,
+}
+
+impl RouteRegistry {
+    /// Create a new registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bind a new subscription of the connection, returning the id handed to the caller
+    ///
+    /// The id is the connection's `server_id`, unless it isn't a hex id or another connection handed out
+    /// the same id already.
+    pub fn bind(&self, server_id: &str, binding: RouteBinding) -> uuid::Uuid {
+        let mut bindings = lock(&self.bindings);
+        let mut id =
+            parse_uuid_simple(server_id).unwrap_or_else(|_| uuid::Uuid::from_u128(rand::random()));
+        while bindings.contains_key(&id) {
+            id = uuid::Uuid::from_u128(rand::random());
+        }
+        bindings.insert(id, binding);
+        id
+    }
+
+    /// Move the subscription to another connection
+    ///
+    /// Returns whether the subscription is still bound, ie wasn't released in the meantime.
+    pub fn rebind(&self, id: uuid::Uuid, binding: RouteBinding) -> bool {
+        match lock(&self.bindings).get_mut(&id) {
+            Some(bound) => {
+                *bound = binding;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Point every subscription routed by the router at the outbound queue, eg of the connection replacing its own
+    ///
+    /// Subscriptions dropped afterwards are then unsubscribed on the connection that re-issued them.
+    pub fn rebind_outbound(
+        &self,
+        router: &Arc<Mutex<SubscriptionRouter>>,
+        outbound: &mpsc::UnboundedSender<String>,
+    ) {
+        for binding in lock(&self.bindings).values_mut() {
+            if Arc::ptr_eq(&binding.router, router) {
+                binding.outbound = outbound.clone();
+            }
+        }
+    }
+
+    /// Release the subscription, returning the connection it was routed by
+    pub fn unbind(&self, id: uuid::Uuid) -> Option<RouteBinding> {
+        lock(&self.bindings).remove(&id)
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::{HashMap, VecDeque};
+
+use tokio::sync::{mpsc, oneshot};
+
+use super::pending::PendingRequests;
+use crate::messages::inbound::format_uuid_simple;
+
+/// The maximum number of notifications buffered for subscriptions that aren't routed yet
+pub const MAX_ORPHANED_NOTIFICATIONS: usize = 1024;
+
+/// The default number of notifications buffered for each subscription until they are received
+pub const DEFAULT_NOTIFICATION_BUFFER_SIZE: usize = 1024;
+
+/// A route from a subscription id issued by the connection to the caller's subscription
+#[derive(Debug, Clone)]
+struct Route {
+    /// The subscription id handed to the caller
+    id: uuid::Uuid,
+    /// The `eth_subscribe` params, used to re-issue the subscription after a reconnect
+    params: Vec<serde_json::Value>,
+    /// The channel notifications are delivered on
+    sender: mpsc::Sender<serde_json::Value>,
+}
+
+/// Routes inbound websocket messages to their subscriptions and waiting requests
+///
+/// `eth_subscription` notifications are delivered on the channel of their subscription, with the
+/// subscription id rewritten to the id handed to the caller, so subscriptions keep their id across
+/// reconnects. Every other message carrying an `id` is a json-rpc response, delivered to the
+/// request [waiting](SubscriptionRouter::wait) on it regardless of the order responses arrive in.
+///
+/// The router is also the registry of open subscriptions, which are re-issued from their
+/// params and [rerouted](SubscriptionRouter::reroute_all) after a reconnect.
+///
+/// Notifications can arrive before their subscription is routed, as the server may send them right
+/// after confirming the `eth_subscribe`. These are buffered (up to [MAX_ORPHANED_NOTIFICATIONS]) and
+/// delivered once the route is inserted.
+#[derive(Debug, Default)]
+pub struct SubscriptionRouter {
+    /// Routes keyed by the subscription id issued by the current connection, verbatim
+    routes: HashMap<String, Route>,
+    /// Notifications received before their subscription was routed
+    orphans: VecDeque<(String, serde_json::Value)>,
+    /// The requests waiting on their json-rpc response
+    pending: PendingRequests,
+}
+
+impl SubscriptionRouter {
+    /// Create a new router
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait on the json-rpc response to the request id
+    pub fn wait(&mut self, id: u64) -> oneshot::Receiver<serde_json::Value> {
+        self.pending.insert(id)
+    }
+
+    /// Wait on an error rejecting the batch of request ids as a whole, see [PendingRequests::insert_batch]
+    pub fn wait_batch(&mut self, ids: &[u64]) -> oneshot::Receiver<serde_json::Value> {
+        self.pending.insert_batch(ids)
+    }
+
+    /// Stop waiting on the json-rpc response to the request id
+    pub fn cancel(&mut self, id: u64) {
+        self.pending.remove(id);
+    }
+
+    /// Route notifications for the connection's `server_id` to the caller's subscription `id`
+    pub fn insert(
+        &mut self,
+        server_id: String,
+        id: uuid::Uuid,
+        params: Vec<serde_json::Value>,
+        sender: mpsc::Sender<serde_json::Value>,
+    ) {
+        let route = Route { id, params, sender };
+        self.insert_route(server_id, route);
+    }
+
+    /// Move the route of a previous connection's `old_server_id` to the current connection's `new_server_id`
+    pub fn reroute(&mut self, old_server_id: &str, new_server_id: String) {
+        if let Some(route) = self.routes.remove(old_server_id) {
+            self.insert_route(new_server_id, route);
+        }
+    }
+
+    /// Move the routes of a previous connection's subscription ids to the current connection's ids all at once
+    ///
+    /// Routes without a new id are removed. Moving every route at once keeps a route from replacing another
+    /// whose previous id the current connection handed out again.
+    pub fn reroute_all(&mut self, moves: Vec<(String, Option<String>)>) {
+        let routes = moves
+            .into_iter()
+            .filter_map(|(old_server_id, new_server_id)| {
+                let route = self.routes.remove(&old_server_id)?;
+                new_server_id.map(|new_server_id| (new_server_id, route))
+            })
+            .collect::<Vec<_>>();
+        for (new_server_id, route) in routes {
+            self.insert_route(new_server_id, route);
+        }
+    }
+
+    /// Remove the route of the caller's subscription `id`, returning the connection's subscription id
+    pub fn remove(&mut self, id: uuid::Uuid) -> Option<String> {
+        let server_id = self
+            .routes
+            .iter()
+            .find(|(_, route)| route.id == id)
+            .map(|(server_id, _)| server_id.clone())?;
+        self.routes.remove(&server_id);
+        Some(server_id)
+    }
+
+    /// Remove the route of the connection's `server_id`, ending its subscription
+    pub fn remove_server_id(&mut self, server_id: &str) {
+        self.routes.remove(server_id);
+    }
+
+    /// The connection's subscription ids and `eth_subscribe` params of every routed subscription
+    pub fn subscriptions(&self) -> Vec<(String, Vec<serde_json::Value>)> {
+        self.routes
+            .iter()
+            .map(|(server_id, route)| (server_id.clone(), route.params.clone()))
+            .collect()
+    }
+
+    /// Remove every route, returning the caller's subscription id, params and channel of each
+    ///
+    /// The subscriptions can then be re-issued on another connection, eg by a failover.
+    pub fn drain(
+        &mut self,
+    ) -> Vec<(
+        uuid::Uuid,
+        Vec<serde_json::Value>,
+        mpsc::Sender<serde_json::Value>,
+    )> {
+        self.routes
+            .drain()
+            .map(|(_, route)| (route.id, route.params, route.sender))
+            .collect()
+    }
+
+    /// Whether the caller's subscription `id` is routed
+    pub fn contains(&self, id: uuid::Uuid) -> bool {
+        self.routes.values().any(|route| route.id == id)
+    }
+
+    /// The number of routed subscriptions
+    pub fn len(&self) -> usize {
+        self.routes.len()
+    }
+
+    /// Whether no subscription is routed
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+
+    /// Insert a route, delivering any notifications that arrived before it
+    fn insert_route(&mut self, server_id: String, route: Route) {
+        let orphans = std::mem::take(&mut self.orphans);
+        for (orphan_id, notification) in orphans {
+            if orphan_id == server_id {
+                route.deliver(notification);
+            } else {
+                self.orphans.push_back((orphan_id, notification));
+            }
+        }
+
+        self.routes.insert(server_id, route);
+    }
+
+    /// Remove every route and pending request, ending all subscriptions and failing all requests
+    pub fn clear(&mut self) {
+        self.routes.clear();
+        self.orphans.clear();
+        self.pending.clear();
+    }
+
+    /// Dispatch a raw inbound message
+    ///
+    /// The elements of an array, ie the response to a batch, are dispatched one by one.
+    pub fn dispatch(&mut self, text: &str) {
+        match serde_json::from_str::<serde_json::Value>(text) {
+            Ok(serde_json::Value::Array(messages)) => {
+                for message in messages {
+                    self.route(message);
+                }
+            }
+            Ok(message) => self.route(message),
+            Err(e) => tracing::warn!("Dropping unparseable message: {:?}", e),
+        }
+    }
+
+    /// Route a single inbound message to its subscription or waiting request
+    fn route(&mut self, message: serde_json::Value) {
+        if message.get("method").and_then(|m| m.as_str()) == Some("eth_subscription") {
+            let server_id = message
+                .get("params")
+                .and_then(|p| p.get("subscription"))
+                .and_then(|s| s.as_str())
+                .map(String::from);
+            match server_id {
+                Some(server_id) => match self.routes.get(&server_id) {
+                    Some(route) => {
+                        // The receiver may outlive the router it was created by, eg after a failover
+                        if !route.deliver(message) {
+                            self.routes.remove(&server_id);
+                        }
+                    }
+                    None => self.orphan(server_id, message),
+                },
+                None => {
+                    tracing::warn!("Dropping notification without a subscription: {}", message)
+                }
+            }
+        } else if let Some(id) = message.get("id").and_then(|id| id.as_u64()) {
+            if !self.pending.resolve(id, message) {
+                tracing::debug!("Dropping response nobody waits on: {}", id);
+            }
+        } else if message.get("error").is_some() {
+            // Errors without an id reject a malformed batch as a whole
+            if !self.pending.reject(message.clone()) {
+                tracing::debug!("Dropping error without a request id: {}", message);
+            }
+        } else {
+            tracing::debug!("Dropping unexpected message: {}", message);
+        }
+    }
+
+    /// Buffer a notification for a subscription that isn't routed yet
+    fn orphan(&mut self, server_id: String, message: serde_json::Value) {
+        if self.orphans.len() >= MAX_ORPHANED_NOTIFICATIONS {
+            self.orphans.pop_front();
+        }
+        self.orphans.push_back((server_id, message));
+    }
+}
+
+impl Route {
+    /// Deliver a notification, rewriting its subscription id to the caller's id
+    ///
+    /// Notifications are dropped while the subscription's buffer is full, so a slow receiver
+    /// never holds up the connection. Returns whether the receiver is still around.
+    fn deliver(&self, mut notification: serde_json::Value) -> bool {
+        if let Some(params) = notification.get_mut("params") {
+            params["subscription"] = serde_json::Value::String(format_uuid_simple(&self.id));
+        }
+        match self.sender.try_send(notification) {
+            Ok(()) => true,
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::warn!(
+                    "Dropping notification for full subscription buffer: {}",
+                    self.id
+                );
+                true
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => false,
+        }
+    }
+}
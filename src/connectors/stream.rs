@@ -0,0 +1,74 @@
+use std::{
+    fmt::Debug,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use async_tls::client::TlsStream;
+use futures::io::{AsyncRead, AsyncWrite};
+use tokio::{io::DuplexStream, net::TcpStream};
+use tokio_util::compat::Compat;
+
+/// A byte stream provided by a custom [Transport](super::transport::Transport)
+pub trait TransportStream: AsyncRead + AsyncWrite + Debug + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Debug + Send + Unpin> TransportStream for T {}
+
+/// The byte stream underlying a raw websocket connection
+#[derive(Debug)]
+pub enum RawStream {
+    /// A plain tcp stream, used for `ws://` urls
+    Plain(Compat<TcpStream>),
+    /// A tls-encrypted tcp stream, used for `wss://` urls
+    Tls(Box<TlsStream<Compat<TcpStream>>>),
+    /// A unix domain socket stream
+    #[cfg(unix)]
+    Unix(Compat<tokio::net::UnixStream>),
+    /// An in-memory stream
+    Memory(Compat<DuplexStream>),
+    /// A stream of a custom transport
+    Other(Box<dyn TransportStream>),
+}
+
+/// Forwards a method call to the stream of every variant
+macro_rules! forward {
+    ($stream:expr, $s:ident => $call:expr) => {
+        match $stream {
+            RawStream::Plain($s) => $call,
+            RawStream::Tls($s) => $call,
+            #[cfg(unix)]
+            RawStream::Unix($s) => $call,
+            RawStream::Memory($s) => $call,
+            RawStream::Other($s) => $call,
+        }
+    };
+}
+
+impl AsyncRead for RawStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        forward!(self.get_mut(), s => Pin::new(s).poll_read(cx, buf))
+    }
+}
+
+impl AsyncWrite for RawStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        forward!(self.get_mut(), s => Pin::new(s).poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        forward!(self.get_mut(), s => Pin::new(s).poll_flush(cx))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        forward!(self.get_mut(), s => Pin::new(s).poll_close(cx))
+    }
+}
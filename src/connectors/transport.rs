@@ -0,0 +1,153 @@
+use std::{fmt::Debug, io};
+
+use async_tls::TlsConnector;
+use async_trait::async_trait;
+use tokio::{io::DuplexStream, sync::mpsc};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+use super::{errors::AlchemyConnectionError, stream::RawStream, url::AlchemyUrl};
+
+/// Opens the byte streams raw websocket connections run over
+///
+/// The websocket handshake runs over the opened stream,
+/// so the url still provides the `Host` header and resource of the handshake request.
+#[async_trait]
+pub trait Transport: Debug + Send + Sync {
+    /// Open a new stream for the url
+    async fn open(&self, url: &AlchemyUrl) -> Result<RawStream, AlchemyConnectionError>;
+}
+
+/// A tcp transport, encrypted with tls for `wss://` urls
+#[derive(Clone, Default)]
+pub struct TcpTransport {
+    /// An optional tls connector used in place of the default webpki roots
+    pub tls_connector: Option<TlsConnector>,
+}
+
+impl Debug for TcpTransport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TcpTransport")
+            .field(
+                "tls_connector",
+                &self.tls_connector.as_ref().map(|_| "TlsConnector"),
+            )
+            .finish()
+    }
+}
+
+impl TcpTransport {
+    /// Create a new TcpTransport using the default webpki roots
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Use a custom [TlsConnector](async_tls::TlsConnector) for the tls handshake
+    ///
+    /// This is useful for trusting self-signed certificates, for example in a local test server.
+    pub fn with_tls_connector(mut self, connector: TlsConnector) -> Self {
+        self.tls_connector = Some(connector);
+        self
+    }
+}
+
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn open(&self, url: &AlchemyUrl) -> Result<RawStream, AlchemyConnectionError> {
+        // Create the socket connection
+        let socket = match tokio::net::TcpStream::connect(url.socket_addr()).await {
+            Ok(s) => s,
+            Err(e) => return Err(AlchemyConnectionError::RawStreamError(e)),
+        };
+
+        if !url.is_secure() {
+            return Ok(RawStream::Plain(socket.compat()));
+        }
+
+        // Encrypt the socket
+        let connector = self.tls_connector.clone().unwrap_or_default();
+        match connector.connect(url.domain(), socket.compat()).await {
+            Ok(s) => Ok(RawStream::Tls(Box::new(s))),
+            Err(e) => Err(AlchemyConnectionError::TlsError(e)),
+        }
+    }
+}
+
+/// A unix domain socket transport, eg for a websocket served by a local node
+///
+/// Every url is connected to the same socket path.
+#[cfg(unix)]
+#[derive(Debug, Clone)]
+pub struct UnixTransport {
+    /// The path of the unix domain socket
+    pub path: std::path::PathBuf,
+}
+
+#[cfg(unix)]
+impl UnixTransport {
+    /// Create a new UnixTransport connecting to the socket path
+    pub fn new(path: impl Into<std::path::PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[cfg(unix)]
+#[async_trait]
+impl Transport for UnixTransport {
+    async fn open(&self, _url: &AlchemyUrl) -> Result<RawStream, AlchemyConnectionError> {
+        match tokio::net::UnixStream::connect(&self.path).await {
+            Ok(s) => Ok(RawStream::Unix(s.compat())),
+            Err(e) => Err(AlchemyConnectionError::RawStreamError(e)),
+        }
+    }
+}
+
+/// An in-memory transport over [tokio::io::duplex] pipes, eg to drive a manager in tests without any network
+///
+/// The server half of every opened stream is handed to the paired [MemoryListener](MemoryListener).
+#[derive(Debug, Clone)]
+pub struct MemoryTransport {
+    /// The buffer size of each direction of the pipes
+    buffer_size: usize,
+    /// The queue of server halves accepted by the listener
+    streams: mpsc::UnboundedSender<DuplexStream>,
+}
+
+impl MemoryTransport {
+    /// Create a new MemoryTransport along with the listener accepting its streams
+    pub fn new(buffer_size: usize) -> (Self, MemoryListener) {
+        let (streams, receiver) = mpsc::unbounded_channel();
+        let transport = Self {
+            buffer_size,
+            streams,
+        };
+        (transport, MemoryListener { streams: receiver })
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn open(&self, _url: &AlchemyUrl) -> Result<RawStream, AlchemyConnectionError> {
+        let (client, server) = tokio::io::duplex(self.buffer_size);
+        if self.streams.send(server).is_err() {
+            return Err(AlchemyConnectionError::RawStreamError(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "the memory listener was dropped",
+            )));
+        }
+        Ok(RawStream::Memory(client.compat()))
+    }
+}
+
+/// Accepts the server halves of the streams opened by a [MemoryTransport](MemoryTransport)
+#[derive(Debug)]
+pub struct MemoryListener {
+    /// The queue of server halves
+    streams: mpsc::UnboundedReceiver<DuplexStream>,
+}
+
+impl MemoryListener {
+    /// Wait for the next opened stream, returning `None` once every transport was dropped
+    pub async fn accept(&mut self) -> Option<DuplexStream> {
+        self.streams.recv().await
+    }
+}
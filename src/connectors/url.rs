@@ -0,0 +1,217 @@
+use std::{fmt, str::FromStr};
+
+use super::errors::AlchemyConnectionError;
+
+/// The scheme of a websocket url
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WsScheme {
+    /// A plain websocket connection, eg to a local node
+    Ws,
+    /// A tls-encrypted websocket connection, eg to the Alchemy API
+    Wss,
+}
+
+impl WsScheme {
+    /// The default port for the scheme
+    pub fn default_port(&self) -> u16 {
+        match self {
+            WsScheme::Ws => 80,
+            WsScheme::Wss => 443,
+        }
+    }
+}
+
+impl fmt::Display for WsScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WsScheme::Ws => write!(f, "ws"),
+            WsScheme::Wss => write!(f, "wss"),
+        }
+    }
+}
+
+/// A parsed websocket url
+///
+/// Drives the tcp target, the `Host` header and the resource path of the websocket handshake.
+///
+/// ## Example
+///
+/// ```rust
+/// use alchemy_rs::connectors::prelude::*;
+///
+/// let url = AlchemyUrl::parse("wss://eth-mainnet.g.alchemy.com/v2/demo").unwrap();
+/// assert_eq!(url.scheme, WsScheme::Wss);
+/// assert_eq!(url.port, 443);
+/// assert_eq!(url.host_header(), "eth-mainnet.g.alchemy.com");
+/// assert_eq!(url.resource(), "/v2/demo");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlchemyUrl {
+    /// The url scheme
+    pub scheme: WsScheme,
+    /// The url host
+    pub host: String,
+    /// The url port, defaulting to the scheme's port
+    pub port: u16,
+    /// The url path
+    pub path: String,
+    /// The optional url query
+    pub query: Option<String>,
+}
+
+impl AlchemyUrl {
+    /// Parse a `ws://` or `wss://` url
+    pub fn parse(url: &str) -> Result<Self, AlchemyConnectionError> {
+        let parsed = ::url::Url::parse(url)
+            .map_err(|_| AlchemyConnectionError::InvalidUrl(url.to_string()))?;
+
+        let scheme = match parsed.scheme() {
+            "ws" => WsScheme::Ws,
+            "wss" => WsScheme::Wss,
+            other => return Err(AlchemyConnectionError::UnsupportedScheme(other.to_string())),
+        };
+
+        let host = match parsed.host_str() {
+            Some(h) if !h.is_empty() => h.to_string(),
+            _ => return Err(AlchemyConnectionError::InvalidUrl(url.to_string())),
+        };
+
+        Ok(Self {
+            scheme,
+            host,
+            port: parsed.port().unwrap_or_else(|| scheme.default_port()),
+            path: parsed.path().to_string(),
+            query: parsed.query().map(String::from),
+        })
+    }
+
+    /// Resolve a handshake redirect `Location` against this url
+    ///
+    /// Relative locations keep the current scheme, host and port, while `http` and `https`
+    /// locations are mapped to `ws` and `wss` respectively.
+    ///
+    /// As the api key is part of the resource, redirects from `wss` to `ws` are rejected with an
+    /// [InsecureRedirect](AlchemyConnectionError::InsecureRedirect), and redirects to another host
+    /// with a [CrossHostRedirect](AlchemyConnectionError::CrossHostRedirect) unless `allow_cross_host` is set.
+    pub fn redirect(
+        &self,
+        location: &str,
+        allow_cross_host: bool,
+    ) -> Result<Self, AlchemyConnectionError> {
+        let invalid = || AlchemyConnectionError::InvalidUrl(location.to_string());
+        let base = ::url::Url::parse(&self.to_string()).map_err(|_| invalid())?;
+        let mut joined = base.join(location).map_err(|_| invalid())?;
+        let ws_scheme = match joined.scheme() {
+            "http" => Some("ws"),
+            "https" => Some("wss"),
+            _ => None,
+        };
+        if let Some(scheme) = ws_scheme {
+            joined.set_scheme(scheme).map_err(|_| invalid())?;
+        }
+        let redirected = Self::parse(joined.as_str())?;
+
+        if self.is_secure() && !redirected.is_secure() {
+            return Err(AlchemyConnectionError::InsecureRedirect(
+                redirected.to_string(),
+            ));
+        }
+        if !allow_cross_host && !redirected.domain().eq_ignore_ascii_case(self.domain()) {
+            return Err(AlchemyConnectionError::CrossHostRedirect(
+                redirected.to_string(),
+            ));
+        }
+        Ok(redirected)
+    }
+
+    /// The url without its path and query, eg for logs and events, as the api key is part of the resource
+    ///
+    /// Only the scheme and host are shown, which for the Alchemy API still names the network.
+    ///
+    /// ```rust
+    /// use alchemy_rs::connectors::prelude::*;
+    ///
+    /// let url = AlchemyUrl::parse("wss://eth-mainnet.g.alchemy.com/v2/<API_KEY>").unwrap();
+    /// assert_eq!(url.redacted().to_string(), "wss://eth-mainnet.g.alchemy.com");
+    /// ```
+    pub fn redacted(&self) -> RedactedUrl<'_> {
+        RedactedUrl(self)
+    }
+
+    /// Whether the connection must be tls-encrypted
+    pub fn is_secure(&self) -> bool {
+        self.scheme == WsScheme::Wss
+    }
+
+    /// The host name without ipv6 brackets, used for the tcp target and the tls server name
+    pub fn domain(&self) -> &str {
+        self.host.trim_start_matches('[').trim_end_matches(']')
+    }
+
+    /// The tcp target of the connection
+    pub fn socket_addr(&self) -> (&str, u16) {
+        (self.domain(), self.port)
+    }
+
+    /// The value of the `Host` header, only including the port if it is not the scheme default
+    pub fn host_header(&self) -> String {
+        if self.port == self.scheme.default_port() {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    /// The handshake resource, ie the path followed by the query
+    pub fn resource(&self) -> String {
+        let path = if self.path.is_empty() {
+            "/"
+        } else {
+            &self.path
+        };
+        match &self.query {
+            Some(q) => format!("{}?{}", path, q),
+            None => path.to_string(),
+        }
+    }
+}
+
+impl FromStr for AlchemyUrl {
+    type Err = AlchemyConnectionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl fmt::Display for AlchemyUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}://{}{}",
+            self.scheme,
+            self.host_header(),
+            self.resource()
+        )
+    }
+}
+
+/// An [AlchemyUrl] displayed without its path and query, see [redacted](AlchemyUrl::redacted)
+#[derive(Debug, Clone, Copy)]
+pub struct RedactedUrl<'a>(&'a AlchemyUrl);
+
+impl fmt::Display for RedactedUrl<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}://{}", self.0.scheme, self.0.host_header())
+    }
+}
+
+/// Formats a websocket url without its path and query, see [redacted](AlchemyUrl::redacted)
+///
+/// Urls that can't be parsed are left out entirely.
+pub fn redact_url(url: &str) -> String {
+    match AlchemyUrl::parse(url) {
+        Ok(url) => url.redacted().to_string(),
+        Err(_) => String::from("<invalid url>"),
+    }
+}
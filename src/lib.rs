@@ -14,16 +14,37 @@ pub mod messages;
 /// Alchemy Manager
 pub mod manager;
 
+/// Alchemy Manager Builder
+pub mod builder;
+
+/// A shareable Alchemy client handle
+pub mod client;
+
+/// Alchemy Networks
+pub mod network;
+
 /// ethers-rs Alchemy wrappers
 pub mod wrapper;
 
 /// Common types
 pub mod types;
 
+/// Subscription streams
+pub mod subscription;
+
+/// Racing redundant subscription feeds
+pub mod racing;
+
 /// A prelude of commonly used alchemy-rs items
 pub mod prelude {
-    pub use super::{manager::*, messages::prelude::*, types::*, wrapper::*};
+    pub use super::{
+        builder::*, client::*, manager::*, messages::prelude::*, network::*, racing::*, types::*,
+        wrapper::*,
+    };
 
     // Re-export ethers-rs prelude
     pub use ethers::prelude::*;
+
+    // Shadows the ethers-rs subscription stream
+    pub use super::subscription::SubscriptionStream;
 }
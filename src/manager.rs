@@ -1,15 +1,72 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
 use ethers::prelude::*;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::{broadcast, mpsc};
 
 use crate::connectors::prelude::*;
 use crate::messages::prelude::*;
+use crate::{
+    builder::AlchemyManagerBuilder,
+    client::AlchemyClient,
+    network::{AlchemyNetwork, Capability},
+    racing::{Raced, RacingStream},
+    subscription::SubscriptionStream,
+};
 
 /// An alchemy api connection manager
 #[derive(Debug)]
 pub struct AlchemyManager {
-    /// The raw alchemy connection url
+    /// The raw alchemy connection url, the http url of an http connector, or the socket path of an ipc connector
     pub url: String,
+    /// The network the url serves, if known
+    pub network: Option<AlchemyNetwork>,
+    /// Whether to verify the chain id of the connection against the network on connect
+    pub verify_chain_id: bool,
     /// The connector to the alchemy api
     pub connector: AlchemyConnector,
+    /// The policy the reader task reconnects with after the connection drops
+    pub reconnect_policy: ReconnectPolicy,
+    /// The keepalive configuration of raw connections, disabling pings if `None`
+    pub keepalive: Option<KeepaliveConfig>,
+    /// The time to wait for the response to a json-rpc request
+    pub request_timeout: Duration,
+    /// The time allowed to establish a connection, unlimited if `None`
+    pub connect_timeout: Option<Duration>,
+    /// Additional headers sent with the handshake request of raw connections
+    pub headers: Vec<(String, String)>,
+    /// The maximum size of an inbound message on raw connections, using soketto's default if `None`
+    pub max_message_size: Option<usize>,
+    /// The transport opening the stream of raw connections
+    pub transport: Arc<dyn Transport>,
+    /// The interval the filters emulating subscriptions over http are polled at
+    pub poll_interval: Duration,
+    /// The number of notifications buffered for each subscription until they are received
+    pub notification_buffer_size: usize,
+    /// The number of connections of a pool connector
+    pub pool_size: usize,
+    /// The maximum number of subscriptions placed on one connection of a pool connector, unlimited if `None`
+    pub max_subscriptions_per_connection: Option<usize>,
+    /// The urls of the backup endpoints of a failover connector, in order of preference
+    pub backup_urls: Vec<String>,
+    /// The health probing of a failover connector
+    pub failover: FailoverConfig,
+    /// Routes inbound messages from the reader task to subscriptions and waiting requests
+    router: Arc<Mutex<SubscriptionRouter>>,
+    /// The connections subscriptions are routed by, shared by every client
+    registry: Arc<RouteRegistry>,
+    /// Allocates the ids of outbound json-rpc requests, shared by every client
+    request_ids: Arc<RequestIdAllocator>,
+    /// The notification channels of subscriptions polled with [receive](AlchemyManager::receive)
+    receivers: HashMap<uuid::Uuid, mpsc::Receiver<serde_json::Value>>,
+    /// The tasks forwarding the notifications of provider subscriptions, and polling those of http subscriptions
+    forwarders: HashMap<uuid::Uuid, Forwarder>,
+    /// The chain id reported by the connection
+    chain_id: Option<u64>,
 }
 
 impl AlchemyManager {
@@ -17,43 +74,375 @@ impl AlchemyManager {
     pub fn new(url: &str, ty: Option<AlchemyConnectorType>) -> Self {
         Self {
             url: url.to_string(),
+            network: None,
+            verify_chain_id: false,
             connector: ty.unwrap_or_default().into(),
+            reconnect_policy: ReconnectPolicy::default(),
+            keepalive: Some(KeepaliveConfig::default()),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            connect_timeout: None,
+            headers: vec![],
+            max_message_size: None,
+            transport: Arc::new(TcpTransport::new()),
+            poll_interval: DEFAULT_FILTER_POLL_INTERVAL,
+            notification_buffer_size: DEFAULT_NOTIFICATION_BUFFER_SIZE,
+            pool_size: DEFAULT_POOL_SIZE,
+            max_subscriptions_per_connection: None,
+            backup_urls: vec![],
+            failover: FailoverConfig::default(),
+            router: Arc::new(Mutex::new(SubscriptionRouter::new())),
+            registry: Arc::new(RouteRegistry::new()),
+            request_ids: Arc::new(RequestIdAllocator::new()),
+            receivers: HashMap::new(),
+            forwarders: HashMap::new(),
+            chain_id: None,
         }
     }
 
+    /// Create a new AlchemyManager for the network's websocket endpoint
+    pub fn from_network(
+        network: AlchemyNetwork,
+        api_key: &str,
+        ty: Option<AlchemyConnectorType>,
+    ) -> Self {
+        let mut manager = Self::new(&network.ws_url(api_key), ty);
+        manager.network = Some(network);
+        manager
+    }
+
+    /// Create an [AlchemyManagerBuilder](AlchemyManagerBuilder), eg to configure the manager from an api key
+    pub fn builder() -> AlchemyManagerBuilder {
+        AlchemyManagerBuilder::new()
+    }
+
+    /// Set the [ReconnectPolicy](ReconnectPolicy) used when the connection drops
+    pub fn with_reconnect_policy(mut self, policy: ReconnectPolicy) -> Self {
+        self.reconnect_policy = policy;
+        self
+    }
+
+    /// Set the [KeepaliveConfig](KeepaliveConfig) of raw connections, disabling pings if `None`
+    ///
+    /// A dead connection detected by the keepalive is reconnected like any other dropped connection.
+    pub fn with_keepalive(mut self, keepalive: Option<KeepaliveConfig>) -> Self {
+        self.keepalive = keepalive;
+        self
+    }
+
+    /// Set the time to wait for the response to a json-rpc request
+    ///
+    /// Requests sent right before the connection drops are never answered, and fail once the timeout elapses.
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+
+    /// Set the [Transport](Transport) opening the stream of raw connections
+    ///
+    /// A [MemoryTransport](MemoryTransport) drives the manager without any network, eg in tests.
+    pub fn with_transport(mut self, transport: Arc<dyn Transport>) -> Self {
+        self.transport = transport;
+        self
+    }
+
+    /// Set the interval the filters emulating subscriptions over http are polled at
+    pub fn with_poll_interval(mut self, interval: Duration) -> Self {
+        self.poll_interval = interval;
+        self
+    }
+
+    /// Set the number of notifications buffered for each subscription until they are received
+    ///
+    /// Notifications arriving while a subscription's buffer is full are dropped.
+    pub fn with_notification_buffer_size(mut self, size: usize) -> Self {
+        self.notification_buffer_size = size;
+        self
+    }
+
+    /// Set the number of connections of a [Pool](AlchemyConnectorType::Pool) connector
+    pub fn with_pool_size(mut self, size: usize) -> Self {
+        self.pool_size = size;
+        self
+    }
+
+    /// Set the maximum number of subscriptions placed on one connection of a [Pool](AlchemyConnectorType::Pool) connector
+    ///
+    /// Subscribing fails with a [PoolExhausted](AlchemyConnectionError::PoolExhausted) once every connection is full.
+    pub fn with_max_subscriptions_per_connection(mut self, max: Option<usize>) -> Self {
+        self.max_subscriptions_per_connection = max;
+        self
+    }
+
+    /// Add a backup endpoint to fail over to with a [Failover](AlchemyConnectorType::Failover) connector
+    ///
+    /// Backups are preferred in the order they are added, after the primary [url](AlchemyManager::url).
+    pub fn with_backup_url(mut self, url: &str) -> Self {
+        self.backup_urls.push(url.to_string());
+        self
+    }
+
+    /// Set the [FailoverConfig](FailoverConfig) probing the endpoints of a [Failover](AlchemyConnectorType::Failover) connector
+    pub fn with_failover_config(mut self, config: FailoverConfig) -> Self {
+        self.failover = config;
+        self
+    }
+
+    /// Set whether to verify the chain id of the connection against the [network](AlchemyManager::network) on connect
+    pub fn with_chain_id_verification(mut self, verify: bool) -> Self {
+        self.verify_chain_id = verify;
+        self
+    }
+
+    /// The chain id reported by the connection
+    ///
+    /// The chain id is fetched with `eth_chainId` on first use, unless it was already verified on connect,
+    /// and cached until the manager connects again.
+    pub async fn chain_id(&mut self) -> Result<u64, AlchemyConnectionError> {
+        if let Some(chain_id) = self.chain_id {
+            return Ok(chain_id);
+        }
+        let chain_id = self.request::<_, U64>("eth_chainId", ()).await?.as_u64();
+        self.chain_id = Some(chain_id);
+        Ok(chain_id)
+    }
+
     /// Connect to the underlying [AlchemyConnector](AlchemyConnector)
     ///
+    /// Raw connections are handed to a background reader task, which routes every
+    /// notification to its subscription and reconnects when the connection drops.
+    /// Connecting an already connected raw connector replaces the connection and
+    /// re-issues every open subscription, which is then unsubscribed on the new connection once dropped.
+    ///
+    /// Ipc connections to the socket path in [url](AlchemyManager::url) work the same way,
+    /// but aren't re-established once they drop. Connecting an already connected ipc connector
+    /// ends every subscription of the previous connection.
+    /// Http connections only prepare the connection pool, without sending anything,
+    /// and emulate subscriptions by polling filters every [poll_interval](AlchemyManager::poll_interval).
+    ///
+    /// Pool connectors establish [pool_size](AlchemyManager::pool_size) raw connections, each with a reader
+    /// task of its own, and replace every connection when connected again. New subscriptions are placed
+    /// on the connection with the fewest subscriptions, and requests sent over it.
+    ///
+    /// Failover connectors connect to the [url](AlchemyManager::url) and every [backup url](AlchemyManager::backup_urls),
+    /// succeeding as long as one of them could be connected, and replace every connection when connected again.
+    /// Requests are sent to the healthiest endpoint, and subscriptions moved over once the active endpoint dies,
+    /// see [FailoverConnection](FailoverConnection).
+    ///
+    /// With [chain id verification](AlchemyManager::with_chain_id_verification), `eth_chainId` is called
+    /// right after connecting, and a connection serving another chain than the configured network is
+    /// dropped with a [ChainIdMismatch](AlchemyConnectionError::ChainIdMismatch).
+    ///
     /// ## Return
     ///
     /// Returns a self reference to allow for method chaining.
     ///
     pub async fn connect(&mut self) -> Result<&Self, AlchemyConnectionError> {
+        self.chain_id = None;
         match &mut self.connector {
             AlchemyConnector::Provider(None) => {
-                let provider = Provider::connect(self.url.clone())
-                    .await
-                    .map_err(AlchemyConnectionError::ProviderError)?;
+                let connecting = Provider::connect(self.url.clone());
+                let provider = match self.connect_timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, connecting)
+                        .await
+                        .map_err(|_| AlchemyConnectionError::ConnectTimeout(timeout))?,
+                    None => connecting.await,
+                }
+                .map_err(AlchemyConnectionError::ProviderError)?;
                 self.connector = AlchemyConnector::Provider(Some(provider.into()));
             }
-            AlchemyConnector::Raw(None) => {
-                let mut conn = RawAlchemyConnection::new();
-                conn.connect(&self.url).await?;
-                self.connector = AlchemyConnector::Raw(Some(conn));
-            }
             AlchemyConnector::Provider(Some(ref mut c)) => match c.connect(&self.url.clone()).await
             {
                 Ok(_) => (),
                 Err(e) => return Err(e),
             },
-            AlchemyConnector::Raw(Some(ref mut c)) => match c.connect(&self.url.clone()).await {
-                Ok(_) => (),
-                Err(e) => return Err(e),
-            },
+            AlchemyConnector::Raw(_) => {
+                let config = self.raw_config();
+                let connection = config.connect().await?;
+
+                // Shut the previous connection down before the new one takes over the router
+                self.connector = AlchemyConnector::Raw(None);
+                let handle = RawConnectionHandle::spawn(connection, config, self.router.clone());
+                self.registry
+                    .rebind_outbound(&self.router, &handle.outbound());
+                self.connector = AlchemyConnector::Raw(Some(handle));
+            }
+            AlchemyConnector::Ipc(_) => {
+                // Close the previous connection first, ending its subscriptions before the new one takes over the router
+                if let AlchemyConnector::Ipc(Some(previous)) =
+                    std::mem::replace(&mut self.connector, AlchemyConnector::Ipc(None))
+                {
+                    previous.close().await;
+                }
+
+                let connecting = IpcConnectionHandle::connect(&self.url, self.router.clone());
+                let handle = match self.connect_timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, connecting)
+                        .await
+                        .map_err(|_| AlchemyConnectionError::ConnectTimeout(timeout))??,
+                    None => connecting.await?,
+                };
+                self.connector = AlchemyConnector::Ipc(Some(handle));
+            }
+            AlchemyConnector::Http(_) => {
+                let connection = self.http_config().connect()?;
+                self.connector = AlchemyConnector::Http(Some(connection));
+            }
+            AlchemyConnector::Pool(_) => {
+                let config = self.raw_config();
+                match &mut self.connector {
+                    AlchemyConnector::Pool(Some(pool)) => {
+                        pool.reconnect(&config, &self.registry).await?
+                    }
+                    _ => {
+                        let pool = ConnectionPool::connect(
+                            &config,
+                            self.pool_size,
+                            self.max_subscriptions_per_connection,
+                        )
+                        .await?;
+                        self.connector = AlchemyConnector::Pool(Some(pool));
+                    }
+                }
+            }
+            AlchemyConnector::Failover(_) => {
+                let primary = self.raw_config();
+                let backups = self.backup_urls.iter().map(|url| RawConnectionConfig {
+                    url: url.clone(),
+                    ..primary.clone()
+                });
+                let configs = std::iter::once(primary.clone()).chain(backups).collect();
+                let connection = FailoverConnection::connect(
+                    configs,
+                    self.failover.clone(),
+                    self.registry.clone(),
+                    self.request_ids.clone(),
+                    self.request_timeout,
+                    self.notification_buffer_size,
+                    self.network,
+                )
+                .await?;
+                self.connector = AlchemyConnector::Failover(Some(connection));
+            }
+        }
+
+        if self.verify_chain_id {
+            self.verify_chain().await?;
         }
 
         Ok(self)
     }
 
+    /// Fetches the chain id of the connection, dropping the connection if it doesn't match the network
+    async fn verify_chain(&mut self) -> Result<(), AlchemyConnectionError> {
+        let chain_id = match self.request::<_, U64>("eth_chainId", ()).await {
+            Ok(chain_id) => chain_id.as_u64(),
+            Err(e) => {
+                self.disconnect();
+                return Err(e);
+            }
+        };
+
+        if let Some(network) = self.network {
+            if network.chain_id() != chain_id {
+                tracing::warn!(
+                    "Connected to chain {} while expecting {} ({})",
+                    chain_id,
+                    network.chain_id(),
+                    network
+                );
+                self.disconnect();
+                return Err(AlchemyConnectionError::ChainIdMismatch(
+                    network.chain_id(),
+                    chain_id,
+                ));
+            }
+        }
+
+        self.chain_id = Some(chain_id);
+        Ok(())
+    }
+
+    /// A shareable [AlchemyClient](AlchemyClient) for the raw or ipc connection
+    ///
+    /// The client can be cloned and handed to other tasks, which can then issue requests and
+    /// subscribe concurrently with the manager over the same connection.
+    /// The client of a pool is bound to the connection with the fewest subscriptions at the time.
+    pub fn client(&self) -> Result<AlchemyClient, AlchemyConnectionError> {
+        let (outbound, router) = match &self.connector {
+            AlchemyConnector::Raw(Some(handle)) => (handle.outbound(), self.router.clone()),
+            AlchemyConnector::Ipc(Some(handle)) => (handle.outbound(), self.router.clone()),
+            AlchemyConnector::Pool(Some(pool)) => {
+                return Ok(self.pooled_client(pool.least_loaded()?))
+            }
+            AlchemyConnector::Failover(Some(failover)) => return failover.client(),
+            AlchemyConnector::Provider(Some(_)) | AlchemyConnector::Http(Some(_)) => {
+                return Err(AlchemyConnectionError::Unimplemented)
+            }
+            AlchemyConnector::Raw(None)
+            | AlchemyConnector::Ipc(None)
+            | AlchemyConnector::Provider(None)
+            | AlchemyConnector::Http(None)
+            | AlchemyConnector::Pool(None)
+            | AlchemyConnector::Failover(None) => {
+                return Err(AlchemyConnectionError::MissingConnection)
+            }
+        };
+        Ok(AlchemyClient::new(
+            outbound,
+            router,
+            self.registry.clone(),
+            self.request_ids.clone(),
+            self.request_timeout,
+            self.notification_buffer_size,
+            self.network,
+        ))
+    }
+
+    /// A client bound to one connection of the pool
+    fn pooled_client(&self, connection: &PooledConnection) -> AlchemyClient {
+        AlchemyClient::new(
+            connection.outbound(),
+            connection.router(),
+            self.registry.clone(),
+            self.request_ids.clone(),
+            self.request_timeout,
+            self.notification_buffer_size,
+            self.network,
+        )
+    }
+
+    /// The client new subscriptions are issued with, placing them on a pool connection with room left
+    fn subscription_client(&self) -> Result<AlchemyClient, AlchemyConnectionError> {
+        match &self.connector {
+            AlchemyConnector::Pool(Some(pool)) => Ok(self.pooled_client(pool.place()?)),
+            AlchemyConnector::Failover(Some(failover)) => failover.subscription_client(),
+            _ => self.client(),
+        }
+    }
+
+    /// Fails if the configured network doesn't serve the capability
+    ///
+    /// Without a configured network, everything is assumed to be served.
+    fn require(&self, capability: Option<Capability>) -> Result<(), AlchemyConnectionError> {
+        match (self.network, capability) {
+            (Some(network), Some(capability)) => network.require(capability),
+            _ => Ok(()),
+        }
+    }
+
+    /// Drops the connection, keeping the connector type
+    fn disconnect(&mut self) {
+        self.chain_id = None;
+        self.connector = match self.connector {
+            AlchemyConnector::Provider(_) => AlchemyConnector::Provider(None),
+            AlchemyConnector::Raw(_) => AlchemyConnector::Raw(None),
+            AlchemyConnector::Ipc(_) => AlchemyConnector::Ipc(None),
+            AlchemyConnector::Http(_) => AlchemyConnector::Http(None),
+            AlchemyConnector::Pool(_) => AlchemyConnector::Pool(None),
+            AlchemyConnector::Failover(_) => AlchemyConnector::Failover(None),
+        };
+    }
+
     /// Subscribes to pending transactions
     ///
     /// ## Arguments
@@ -112,136 +501,411 @@ impl AlchemyManager {
         to: Option<Address>,
         from: Option<Address>,
     ) -> Result<uuid::Uuid, AlchemyConnectionError> {
-        // Extract the internal connection
-        let connection = match &mut self.connector {
-            AlchemyConnector::Raw(Some(raw_conn)) => raw_conn,
-            AlchemyConnector::Provider(Some(_)) => {
-                return Err(AlchemyConnectionError::Unimplemented)
-            }
-            AlchemyConnector::Raw(None) | AlchemyConnector::Provider(None) => {
-                return Err(AlchemyConnectionError::MissingConnection)
+        let (sub_id, receiver) = self
+            .open_subscription(pending_transaction_params(to, from))
+            .await?;
+        self.receivers.insert(sub_id, receiver);
+        Ok(sub_id)
+    }
+
+    /// Subscribes to pending transactions, returning a stream of the transactions
+    ///
+    /// ## Arguments
+    ///
+    /// * `to` - The address to filter transactions sent to
+    /// * `from` - The address to filter transactions sent from
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use alchemy_rs::prelude::*;
+    /// use futures::StreamExt;
+    ///
+    /// async {
+    ///     let mut manager = AlchemyManager::new("wss://eth-mainnet.g.alchemy.com/v2/<API_KEY>", None);
+    ///     manager.connect().await.unwrap();
+    ///
+    ///     let mut stream = manager.subscribe_pending_transactions(None, None).await.unwrap();
+    ///     while let Some(Ok(transaction)) = stream.next().await {
+    ///         println!("Received pending transaction: {:?}", transaction.hash);
+    ///     }
+    /// };
+    /// ```
+    pub async fn subscribe_pending_transactions(
+        &mut self,
+        to: Option<Address>,
+        from: Option<Address>,
+    ) -> Result<SubscriptionStream<Transaction>, AlchemyConnectionError> {
+        self.subscribe_stream(pending_transaction_params(to, from))
+            .await
+    }
+
+    /// Subscribes to new block headers with a standard `newHeads` subscription
+    pub async fn subscribe_new_heads(
+        &mut self,
+    ) -> Result<SubscriptionStream<Block<TxHash>>, AlchemyConnectionError> {
+        self.subscribe_stream(vec!["newHeads".into()]).await
+    }
+
+    /// Subscribes to the logs matching the filter with a standard `logs` subscription
+    ///
+    /// Only the filter's addresses and topics are considered.
+    pub async fn subscribe_logs(
+        &mut self,
+        filter: &Filter,
+    ) -> Result<SubscriptionStream<Log>, AlchemyConnectionError> {
+        self.subscribe_stream(log_params(filter)?).await
+    }
+
+    /// Subscribes to the hashes of new pending transactions with a standard `newPendingTransactions` subscription
+    ///
+    /// Unlike [subscribe_pending_transactions](AlchemyManager::subscribe_pending_transactions),
+    /// this is served by any node, eg over an [Ipc](AlchemyConnectorType::Ipc) connector.
+    pub async fn subscribe_new_pending_transactions(
+        &mut self,
+    ) -> Result<SubscriptionStream<TxHash>, AlchemyConnectionError> {
+        self.subscribe_stream(vec!["newPendingTransactions".into()])
+            .await
+    }
+
+    /// Subscribes with the given `eth_subscribe` params, returning a stream of the notification results
+    ///
+    /// The stream is independent of the manager, and dropping it unsubscribes.
+    /// Enhanced subscriptions the configured network doesn't serve fail early with an
+    /// [UnsupportedOnNetwork](AlchemyConnectionError::UnsupportedOnNetwork).
+    pub async fn subscribe_stream<T: DeserializeOwned>(
+        &mut self,
+        params: Vec<serde_json::Value>,
+    ) -> Result<SubscriptionStream<T>, AlchemyConnectionError> {
+        self.require(Capability::for_subscription(&params))?;
+        match &self.connector {
+            AlchemyConnector::Provider(_) | AlchemyConnector::Http(_) => {
+                let (sub_id, receiver) = self.open_subscription(params).await?;
+                Ok(SubscriptionStream::new(
+                    sub_id,
+                    receiver,
+                    self.registry.clone(),
+                ))
             }
-        };
+            _ => self.subscription_client()?.subscribe_stream(params).await,
+        }
+    }
 
-        // Example Message body
-        // { "id": 1, "method": "eth_subscribe", "params": [ "alchemy_pendingTransactions", { "toAddress": "00000000219ab540356cBB839Cbe05303d7705Fa" } ] }
-
-        let mut param_mapping: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
-        if let Some(t) = to {
-            param_mapping.insert(
-                "toAddress".to_string(),
-                serde_json::Value::String(t.to_string()),
-            );
-        }
-        if let Some(f) = from {
-            param_mapping.insert(
-                "fromAddress".to_string(),
-                serde_json::Value::String(f.to_string()),
-            );
-        }
-
-        // Construct the Alchemy Socket Message
-        let message = AlchemySocketMessage {
-            id: 1,
-            method: OutSocketMethod::Subscribe,
-            params: vec![
-                serde_json::Value::String(String::from("alchemy_pendingTransactions")),
-                serde_json::Value::Object(param_mapping),
-            ],
-        };
+    /// Races a pending transactions subscription on every feed of the connector, delivering each transaction once
+    ///
+    /// See [race_stream](AlchemyManager::race_stream).
+    pub async fn race_pending_transactions(
+        &mut self,
+        to: Option<Address>,
+        from: Option<Address>,
+    ) -> Result<RacingStream<Transaction>, AlchemyConnectionError> {
+        self.race_stream(pending_transaction_params(to, from)).await
+    }
 
-        // Turn the message into stringified json
-        let message_string = match serde_json::to_string(&message) {
-            Ok(s) => s,
-            Err(e) => return Err(AlchemyConnectionError::Deserialization(e)),
-        };
+    /// Opens the subscription on every feed of the connector, racing them in a [RacingStream](RacingStream)
+    ///
+    /// [Pool](AlchemyConnectorType::Pool) connectors race every connection with room left, and
+    /// [Failover](AlchemyConnectorType::Failover) connectors every connected endpoint, each feed indexed by
+    /// its connection or endpoint. Any other connector races a single feed.
+    pub async fn race_stream<T: Raced + DeserializeOwned + Send + 'static>(
+        &mut self,
+        params: Vec<serde_json::Value>,
+    ) -> Result<RacingStream<T>, AlchemyConnectionError> {
+        let clients: Vec<(usize, AlchemyClient)> = match &self.connector {
+            AlchemyConnector::Pool(Some(pool)) => {
+                // Every feed is placed like a single subscription, on each connection with room left
+                let mut streams = vec![];
+                for index in 0..pool.len() {
+                    if let Ok(connection) = pool.place_at(index) {
+                        let client = self.pooled_client(connection);
+                        streams.push((index, client.subscribe_stream(params.clone()).await?));
+                    }
+                }
 
-        // Extract the sender from the contained websocket channel
-        let sender = match &mut connection.sender {
-            Some(s) => s,
-            None => return Err(AlchemyConnectionError::MissingSender),
+                // Fails like a single subscription would once no connection has room left
+                if streams.is_empty() {
+                    pool.place()?;
+                }
+                return Ok(RacingStream::from_indexed(streams));
+            }
+            AlchemyConnector::Failover(Some(failover)) => failover.indexed_clients(),
+            _ => {
+                return Ok(RacingStream::new(vec![
+                    self.subscribe_stream(params).await?,
+                ]))
+            }
         };
-
-        // Send the string message
-        if let Err(e) = sender.send_text(message_string).await {
-            return Err(AlchemyConnectionError::SendFailed(e));
+        if clients.is_empty() {
+            return Err(AlchemyConnectionError::Closed);
         }
 
-        // Flush the send channel
-        if let Err(e) = sender.flush().await {
-            return Err(AlchemyConnectionError::FlushFailed(e));
+        let mut streams = Vec::with_capacity(clients.len());
+        for (index, client) in clients {
+            streams.push((index, client.subscribe_stream(params.clone()).await?));
         }
+        Ok(RacingStream::from_indexed(streams))
+    }
 
-        // Extract the receiver from the contained websocket channel
-        let receiver = match &mut connection.receiver {
-            Some(r) => r,
-            None => return Err(AlchemyConnectionError::MissingReceiver),
+    /// Issues a subscription and routes its notifications to a new channel
+    async fn open_subscription(
+        &mut self,
+        params: Vec<serde_json::Value>,
+    ) -> Result<(uuid::Uuid, mpsc::Receiver<serde_json::Value>), AlchemyConnectionError> {
+        self.require(Capability::for_subscription(&params))?;
+
+        // Provider subscriptions are forwarded, and http subscriptions polled, by a task of their own
+        let (sender, receiver) = mpsc::channel(self.notification_buffer_size);
+        let (sub_id, forwarder) = match &self.connector {
+            AlchemyConnector::Provider(Some(provider)) => {
+                provider.subscribe(params, sender).await?
+            }
+            AlchemyConnector::Http(Some(http)) => {
+                let sub_id = uuid::Uuid::from_u128(self.request_ids.next_id().into());
+                let poller =
+                    poll_subscription(http.clone(), &params, sub_id, self.poll_interval, sender)
+                        .await?;
+                (sub_id, poller)
+            }
+            _ => return self.subscription_client()?.open_subscription(params).await,
         };
+        self.forwarders.retain(|_, f| !f.is_finished());
+        self.forwarders.insert(sub_id, forwarder);
+        Ok((sub_id, receiver))
+    }
 
-        // After sending the message, the alchemy socket should respond with a subscription id
-        // Ex: { "id": 1, "result": "0x79a3295f5d5f4bd7efaac4e1738c7ada", "jsonrpc": "2.0" }
-        let mut data = vec![];
-        match receiver.receive_data(&mut data).await {
-            Ok(soketto::Data::Text(_)) => {
-                // Convert the data into a text string
-                let text = match String::from_utf8(data) {
-                    Ok(s) => s,
-                    Err(_) => return Err(AlchemyConnectionError::InvalidTextString),
+    /// Cancels a subscription with an `eth_unsubscribe`
+    ///
+    /// The subscription's route is removed even if the server fails to respond,
+    /// ending its [SubscriptionStream](SubscriptionStream) if it was subscribed to as a stream.
+    ///
+    /// ## Returns
+    ///
+    /// Whether the server cancelled the subscription.
+    pub async fn unsubscribe(
+        &mut self,
+        sub_id: uuid::Uuid,
+    ) -> Result<bool, AlchemyConnectionError> {
+        self.receivers.remove(&sub_id);
+        match &self.connector {
+            // The forwarder unsubscribes, and the poller uninstalls its filter, before it stops
+            AlchemyConnector::Provider(Some(_)) | AlchemyConnector::Http(Some(_)) => {
+                return match self.forwarders.remove(&sub_id) {
+                    Some(forwarder) => forwarder.stop().await,
+                    None => Err(AlchemyConnectionError::UnknownSubscription(sub_id)),
+                };
+            }
+            AlchemyConnector::Pool(Some(pool)) => {
+                return match pool.find(sub_id) {
+                    Some(connection) => self.pooled_client(connection).unsubscribe(sub_id).await,
+                    None => Err(AlchemyConnectionError::UnknownSubscription(sub_id)),
                 };
-                match serde_json::from_str::<AlchemySubscriptionMessageResult>(&text) {
-                    Ok(asmr) => Ok(asmr.result), // lol
-                    Err(e) => Err(AlchemyConnectionError::Deserialization(e)),
-                }
             }
-            Ok(soketto::Data::Binary(_)) => Err(AlchemyConnectionError::UnexpectedResponseType),
-            Err(soketto::connection::Error::Closed) => Err(AlchemyConnectionError::Closed),
-            Err(e) => Err(AlchemyConnectionError::SomeError(e)),
+            AlchemyConnector::Failover(Some(failover)) => {
+                return match failover.client_for(sub_id) {
+                    Some(client) => client.unsubscribe(sub_id).await,
+                    None => Err(AlchemyConnectionError::UnknownSubscription(sub_id)),
+                };
+            }
+            _ => (),
         }
+
+        self.client()?.unsubscribe(sub_id).await
     }
 
-    /// Receive a socket message from the established websocket connection
-    pub async fn receive(
+    /// Sends a json-rpc request over the connection, returning its typed result
+    ///
+    /// Any method the connection serves can be called, eg `eth_blockNumber` or `eth_getTransactionByHash`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `method` - The json-rpc method
+    /// * `params` - The json-rpc params, usually a tuple or [Vec]
+    ///
+    /// ## Returns
+    ///
+    /// The deserialized `result` of the response, or an [RpcError](AlchemyConnectionError::RpcError)
+    /// if the server answered with a json-rpc error. Methods of a namespace the configured network
+    /// doesn't serve (eg `trace_*`) fail early with an [UnsupportedOnNetwork](AlchemyConnectionError::UnsupportedOnNetwork).
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use alchemy_rs::prelude::*;
+    ///
+    /// async {
+    ///     let mut manager = AlchemyManager::new("wss://eth-mainnet.g.alchemy.com/v2/<API_KEY>", None);
+    ///     manager.connect().await.unwrap();
+    ///
+    ///     let block_number: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    ///     println!("Current block: {}", block_number);
+    /// };
+    /// ```
+    pub async fn request<P: Serialize, R: DeserializeOwned>(
         &mut self,
-        _sub_id: uuid::Uuid,
-    ) -> Result<AlchemySocketMessageResponse, AlchemyConnectionError> {
-        // Extract the internal connection
-        let connection = match &mut self.connector {
-            AlchemyConnector::Raw(Some(raw_conn)) => raw_conn,
+        method: &str,
+        params: P,
+    ) -> Result<R, AlchemyConnectionError> {
+        self.require(Capability::for_method(method))?;
+        match &self.connector {
+            AlchemyConnector::Provider(Some(provider)) => {
+                return provider.request(method, params).await
+            }
+            AlchemyConnector::Http(Some(http)) => return http.request(method, params).await,
+            AlchemyConnector::Failover(Some(failover)) => {
+                return failover.request(method, params).await
+            }
+            _ => (),
+        }
+
+        self.client()?.request(method, params).await
+    }
+
+    /// Subscribe to the [FailoverEvent](FailoverEvent)s emitted whenever a failover connector switches endpoints
+    pub fn failover_events(
+        &self,
+    ) -> Result<broadcast::Receiver<FailoverEvent>, AlchemyConnectionError> {
+        match &self.connector {
+            AlchemyConnector::Failover(Some(failover)) => Ok(failover.events()),
+            AlchemyConnector::Failover(None) => Err(AlchemyConnectionError::MissingConnection),
+            _ => Err(AlchemyConnectionError::Unimplemented),
+        }
+    }
+
+    /// Sends a batch of json-rpc calls in a single message, eg to backfill many receipts in one round trip
+    ///
+    /// The calls are sent as one json-rpc array, over the websocket or in a single http request,
+    /// and the responses matched back to their call by id, whatever order they arrive in.
+    ///
+    /// ## Returns
+    ///
+    /// The result or json-rpc error of every call, in the order of the calls. The batch only fails as a whole
+    /// if it couldn't be sent or wasn't fully answered in time, or with an [RpcError](AlchemyConnectionError::RpcError)
+    /// if the server rejects it as a whole. Over a websocket, such an error carries no request id and rejects the
+    /// oldest batch still waiting on a response.
+    ///
+    /// ## Example
+    ///
+    /// ```rust
+    /// use alchemy_rs::prelude::*;
+    ///
+    /// async {
+    ///     let mut manager = AlchemyManager::new("wss://eth-mainnet.g.alchemy.com/v2/<API_KEY>", None);
+    ///     manager.connect().await.unwrap();
+    ///
+    ///     let mut batch = JsonRpcBatch::new();
+    ///     batch.push("eth_blockNumber", ()).unwrap();
+    ///     batch.push("eth_getTransactionReceipt", [TxHash::zero()]).unwrap();
+    ///     for result in manager.batch(&batch).await.unwrap() {
+    ///         match result {
+    ///             Ok(value) => println!("Result: {}", value),
+    ///             Err(e) => println!("Error: {}", e.message),
+    ///         }
+    ///     }
+    /// };
+    /// ```
+    pub async fn batch(
+        &mut self,
+        batch: &JsonRpcBatch,
+    ) -> Result<Vec<Result<serde_json::Value, JsonRpcError>>, AlchemyConnectionError> {
+        for (method, _) in &batch.calls {
+            self.require(Capability::for_method(method))?;
+        }
+        match &self.connector {
             AlchemyConnector::Provider(Some(_)) => {
                 return Err(AlchemyConnectionError::Unimplemented)
             }
-            AlchemyConnector::Raw(None) | AlchemyConnector::Provider(None) => {
-                return Err(AlchemyConnectionError::MissingConnection)
-            }
-        };
+            AlchemyConnector::Http(Some(http)) => return http.batch(batch).await,
+            _ => (),
+        }
 
-        // Extract the receiver from the contained websocket channel
-        let receiver = match &mut connection.receiver {
+        self.client()?.batch(batch).await
+    }
+
+    /// Receive a socket message for the given subscription
+    ///
+    /// Only messages belonging to the subscription are returned, messages for other
+    /// subscriptions are kept for their own `receive` calls.
+    ///
+    /// If the connection drops, the reader task reconnects according to the manager's
+    /// [ReconnectPolicy](ReconnectPolicy) and re-issues every open subscription, so the
+    /// subscription ids handed out by [subscribe](AlchemyManager::subscribe) remain valid.
+    pub async fn receive(
+        &mut self,
+        sub_id: uuid::Uuid,
+    ) -> Result<AlchemySocketMessageResponse, AlchemyConnectionError> {
+        let receiver = match self.receivers.get_mut(&sub_id) {
             Some(r) => r,
-            None => return Err(AlchemyConnectionError::MissingReceiver),
+            None => return Err(AlchemyConnectionError::UnknownSubscription(sub_id)),
         };
 
-        // We should receive an [AlchemySocketMessageResponse](crate::messages::inbound::AlchemySocketMessageResponse)
-        // on any new `eth_subscription` message
-        let mut data = vec![];
+        // The channel closes once the reader task gives up on the connection, or the provider subscription ends
+        match receiver.recv().await {
+            Some(notification) => {
+                serde_json::from_value::<AlchemySocketMessageResponse>(notification)
+                    .map_err(AlchemyConnectionError::Deserialization)
+            }
+            None => Err(AlchemyConnectionError::Closed),
+        }
+    }
 
-        // TODO: we have to wait for the next message with the given subscription id, not just any message
+    /// The configuration http connections are created with
+    fn http_config(&self) -> HttpConnectionConfig {
+        HttpConnectionConfig {
+            request_timeout: self.request_timeout,
+            connect_timeout: self.connect_timeout,
+            headers: self.headers.clone(),
+            ..HttpConnectionConfig::new(&self.url)
+        }
+    }
 
-        match receiver.receive_data(&mut data).await {
-            Ok(soketto::Data::Text(_)) => {
-                // Convert the data into a text string
-                let text = match String::from_utf8(data) {
-                    Ok(s) => s,
-                    Err(_) => return Err(AlchemyConnectionError::InvalidTextString),
-                };
-                match serde_json::from_str::<AlchemySocketMessageResponse>(&text) {
-                    Ok(asmr) => Ok(asmr),
-                    Err(e) => Err(AlchemyConnectionError::Deserialization(e)),
-                }
-            }
-            Ok(soketto::Data::Binary(_)) => Err(AlchemyConnectionError::UnexpectedResponseType),
-            Err(soketto::connection::Error::Closed) => Err(AlchemyConnectionError::Closed),
-            Err(e) => Err(AlchemyConnectionError::SomeError(e)),
+    /// The configuration raw connections are (re)established with
+    fn raw_config(&self) -> RawConnectionConfig {
+        RawConnectionConfig {
+            url: self.url.clone(),
+            keepalive: self.keepalive.clone(),
+            reconnect_policy: self.reconnect_policy.clone(),
+            headers: self.headers.clone(),
+            connect_timeout: self.connect_timeout,
+            max_message_size: self.max_message_size,
+            transport: Arc::clone(&self.transport),
+            request_timeout: self.request_timeout,
+            request_ids: Arc::clone(&self.request_ids),
         }
     }
 }
+
+/// Builds the `eth_subscribe` params of a `logs` subscription
+pub(crate) fn log_params(
+    filter: &Filter,
+) -> Result<Vec<serde_json::Value>, AlchemyConnectionError> {
+    let filter = serde_json::to_value(filter).map_err(AlchemyConnectionError::Deserialization)?;
+    Ok(vec!["logs".into(), filter])
+}
+
+/// Builds the `eth_subscribe` params of an `alchemy_pendingTransactions` subscription
+pub(crate) fn pending_transaction_params(
+    to: Option<Address>,
+    from: Option<Address>,
+) -> Vec<serde_json::Value> {
+    // Example Message body
+    // { "id": 1, "method": "eth_subscribe", "params": [ "alchemy_pendingTransactions", { "toAddress": "00000000219ab540356cBB839Cbe05303d7705Fa" } ] }
+
+    // Addresses are formatted in full, their display form is abbreviated
+    let mut param_mapping: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
+    if let Some(t) = to {
+        param_mapping.insert(
+            "toAddress".to_string(),
+            serde_json::Value::String(format!("{:?}", t)),
+        );
+    }
+    if let Some(f) = from {
+        param_mapping.insert(
+            "fromAddress".to_string(),
+            serde_json::Value::String(format!("{:?}", f)),
+        );
+    }
+    vec![
+        serde_json::Value::String(String::from("alchemy_pendingTransactions")),
+        serde_json::Value::Object(param_mapping),
+    ]
+}
@@ -34,11 +34,124 @@ pub struct AlchemySubscriptionMessageResult {
     pub jsonrpc: JsonRpc,
 }
 
+/// Alchemy Unsubscribe Message Result
+///
+/// ## Example
+///
+/// After sending an eth_unsubscribe, the alchemy websocket responds whether the subscription was cancelled.
+///
+/// ```json
+/// {
+///     "id": 1,
+///     "result": true,
+///     "jsonrpc": "2.0"
+/// }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AlchemyUnsubscribeMessageResult {
+    /// The message id
+    pub id: u64,
+    /// Whether the subscription was cancelled
+    pub result: bool,
+    /// The message jsonrpc
+    pub jsonrpc: JsonRpc,
+}
+
+/// A JSON-RPC Error Response
+///
+/// ## Example
+///
+/// A failed request is answered with an `error` object in place of the `result`.
+///
+/// ```json
+/// {
+///     "id": 1,
+///     "error": {
+///         "code": -32602,
+///         "message": "invalid 1st argument: transaction_hash value was not valid hexadecimal"
+///     },
+///     "jsonrpc": "2.0"
+/// }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct JsonRpcError {
+    /// The error code
+    pub code: i64,
+    /// The error message
+    pub message: String,
+    /// Additional error data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+}
+
+/// A json-rpc response, carrying either a result or an error
+///
+/// ## Example
+///
+/// ```json
+/// {
+///     "jsonrpc": "2.0",
+///     "id": 1,
+///     "result": "0x10"
+/// }
+/// ```
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct JsonRpcResponse {
+    /// The id of the request, missing if the request could not be read
+    #[serde(default)]
+    pub id: Option<u64>,
+    /// The json rpc version
+    pub jsonrpc: String,
+    /// The result, `null` for error responses
+    #[serde(default)]
+    pub result: serde_json::Value,
+    /// The error, if the request failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<JsonRpcError>,
+}
+
+impl JsonRpcResponse {
+    /// The result of the response, or its error
+    pub fn into_result(self) -> Result<serde_json::Value, JsonRpcError> {
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.result),
+        }
+    }
+}
+
 pub(crate) fn serialize_uuid_simple<S>(uuid: &uuid::Uuid, s: S) -> Result<S::Ok, S::Error>
 where
     S: serde::Serializer,
 {
-    s.serialize_str(&format!("0x{}", &uuid.as_simple()))
+    s.serialize_str(&format_uuid_simple(uuid))
+}
+
+/// Parses a `0x`-prefixed subscription id
+///
+/// Nodes like geth trim the leading zeros of their hex subscription ids, so shorter ids are left-padded first.
+pub(crate) fn parse_uuid_simple(s: &str) -> Result<uuid::Uuid, uuid::Error> {
+    let hex = s.strip_prefix("0x").unwrap_or(s);
+    uuid::Uuid::parse_str(&format!("{:0>32}", hex))
+}
+
+/// Formats a subscription id as a `0x`-prefixed string
+pub(crate) fn format_uuid_simple(uuid: &uuid::Uuid) -> String {
+    format!("0x{}", &uuid.as_simple())
+}
+
+/// The subscription id issued by the connection in response to an `eth_subscribe`, kept verbatim
+///
+/// The id is only ever compared to the ids of notifications and sent back to unsubscribe,
+/// so it is left as the server formatted it.
+pub(crate) fn subscription_id(
+    mut response: serde_json::Value,
+) -> Result<String, serde_json::Error> {
+    let result = response
+        .get_mut("result")
+        .map(serde_json::Value::take)
+        .unwrap_or_default();
+    serde_json::from_value(result)
 }
 
 pub(crate) fn deserialize_uuid_simple<'de, D>(deserializer: D) -> Result<uuid::Uuid, D::Error>
@@ -46,9 +159,7 @@ where
     D: serde::Deserializer<'de>,
 {
     match serde_json::Value::deserialize(deserializer) {
-        Ok(serde_json::Value::String(s)) => {
-            uuid::Uuid::parse_str(&s.replace("0x", "")).map_err(serde::de::Error::custom)
-        }
+        Ok(serde_json::Value::String(s)) => parse_uuid_simple(&s).map_err(serde::de::Error::custom),
         Err(e) => Err(e),
         _ => Err(serde::de::Error::custom(
             "Deserialized invalid serde_json::Value from uuid",
@@ -78,10 +189,20 @@ pub struct AlchemySocketMessageResponse {
     pub jsonrpc: JsonRpc,
     /// The message method
     pub method: EthSubscription,
-    /// The inner message result
+    /// The inner message result, sent as the `params` field
+    #[serde(rename = "params", alias = "result")]
     pub result: AlchemyInnerResponse,
 }
 
+impl AlchemySocketMessageResponse {
+    /// The subscription id the message belongs to
+    pub fn subscription(&self) -> uuid::Uuid {
+        match &self.result {
+            AlchemyInnerResponse::PendingTransactionResult(r) => r.subscription,
+        }
+    }
+}
+
 /// An inner Alchemy Websocket Message Response
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 #[serde(untagged)]
@@ -27,6 +27,17 @@ pub struct AlchemySocketMessage {
     pub params: Vec<serde_json::Value>,
 }
 
+impl AlchemySocketMessage {
+    /// An `eth_unsubscribe` message for the connection's subscription id
+    pub fn unsubscribe(id: u64, subscription: String) -> Self {
+        Self {
+            id,
+            method: OutSocketMethod::Unsubscribe,
+            params: vec![serde_json::Value::String(subscription)],
+        }
+    }
+}
+
 /// An Alchemy Websocket Message Method
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
 pub enum OutSocketMethod {
@@ -37,3 +48,108 @@ pub enum OutSocketMethod {
     #[serde(rename = "eth_unsubscribe")]
     Unsubscribe,
 }
+
+impl OutSocketMethod {
+    /// The json-rpc method name
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OutSocketMethod::Subscribe => "eth_subscribe",
+            OutSocketMethod::Unsubscribe => "eth_unsubscribe",
+        }
+    }
+}
+
+/// An Outbound JSON-RPC Request for an arbitrary method
+///
+/// ## Example
+///
+/// The request should serialize into a json string like:
+/// ```json
+/// {
+///     "jsonrpc": "2.0",
+///     "id": 2,
+///     "method": "eth_getTransactionByHash",
+///     "params": [
+///         "0xf3207c10a9b9e09b4b51d5c783a1ba85b632055d06100c51f2c1a331dc293d65"
+///     ]
+/// }
+/// ```
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct JsonRpcRequest<'a, P> {
+    /// The json rpc version
+    pub jsonrpc: &'a str,
+    /// The request id
+    pub id: u64,
+    /// The request method
+    pub method: &'a str,
+    /// The request params, omitted if zero-sized (eg `()`)
+    #[serde(skip_serializing_if = "is_zst")]
+    pub params: P,
+}
+
+/// A batch of json-rpc calls, sent together as one array of requests
+///
+/// Each call is given its request id when the batch is sent, and the responses are matched back by id,
+/// so results are returned in the order of the calls.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct JsonRpcBatch {
+    /// The method and params of every call, in order
+    pub calls: Vec<(String, serde_json::Value)>,
+}
+
+impl JsonRpcBatch {
+    /// Create a new, empty batch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a call to the batch
+    ///
+    /// Params serializing to `null` (eg `()`) are sent as an empty array.
+    pub fn push<P: Serialize>(&mut self, method: &str, params: P) -> Result<(), serde_json::Error> {
+        let params = match serde_json::to_value(params)? {
+            serde_json::Value::Null => serde_json::Value::Array(vec![]),
+            params => params,
+        };
+        self.calls.push((method.to_string(), params));
+        Ok(())
+    }
+
+    /// The number of calls in the batch
+    pub fn len(&self) -> usize {
+        self.calls.len()
+    }
+
+    /// Whether the batch has no calls
+    pub fn is_empty(&self) -> bool {
+        self.calls.is_empty()
+    }
+
+    /// The requests of the batch, with ids drawn from `next_id`
+    pub fn requests(
+        &self,
+        mut next_id: impl FnMut() -> u64,
+    ) -> Vec<JsonRpcRequest<'_, &serde_json::Value>> {
+        self.calls
+            .iter()
+            .map(|(method, params)| JsonRpcRequest::new(next_id(), method, params))
+            .collect()
+    }
+}
+
+/// Whether the value is zero-sized, eg `()`
+fn is_zst<T>(_: &T) -> bool {
+    std::mem::size_of::<T>() == 0
+}
+
+impl<'a, P> JsonRpcRequest<'a, P> {
+    /// Create a new json-rpc 2.0 request
+    pub fn new(id: u64, method: &'a str, params: P) -> Self {
+        Self {
+            jsonrpc: "2.0",
+            id,
+            method,
+            params,
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::{convert::TryFrom, fmt, str::FromStr};
+
+use ethers::types::Chain;
+
+use crate::connectors::errors::AlchemyConnectionError;
+
+/// A network served by the Alchemy API
+///
+/// ## Example
+///
+/// ```rust
+/// use std::convert::TryFrom;
+///
+/// use alchemy_rs::prelude::*;
+///
+/// let network = AlchemyNetwork::try_from(Chain::Polygon).unwrap();
+/// assert_eq!(network, AlchemyNetwork::PolygonMainnet);
+/// assert_eq!(network.ws_url("<API_KEY>"), "wss://polygon-mainnet.g.alchemy.com/v2/<API_KEY>");
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AlchemyNetwork {
+    /// Ethereum Mainnet
+    #[default]
+    EthMainnet,
+    /// Ethereum Goerli
+    EthGoerli,
+    /// Ethereum Sepolia
+    EthSepolia,
+    /// Ethereum Holesky
+    EthHolesky,
+    /// Polygon PoS Mainnet
+    PolygonMainnet,
+    /// Polygon PoS Mumbai
+    PolygonMumbai,
+    /// Polygon PoS Amoy
+    PolygonAmoy,
+    /// Arbitrum One
+    ArbitrumMainnet,
+    /// Arbitrum Sepolia
+    ArbitrumSepolia,
+    /// Optimism Mainnet
+    OptimismMainnet,
+    /// Optimism Sepolia
+    OptimismSepolia,
+    /// Base Mainnet
+    BaseMainnet,
+    /// Base Sepolia
+    BaseSepolia,
+    /// zkSync Era Mainnet
+    ZksyncMainnet,
+    /// zkSync Era Sepolia
+    ZksyncSepolia,
+}
+
+impl AlchemyNetwork {
+    /// Every network served by the Alchemy API
+    pub const ALL: [AlchemyNetwork; 15] = [
+        AlchemyNetwork::EthMainnet,
+        AlchemyNetwork::EthGoerli,
+        AlchemyNetwork::EthSepolia,
+        AlchemyNetwork::EthHolesky,
+        AlchemyNetwork::PolygonMainnet,
+        AlchemyNetwork::PolygonMumbai,
+        AlchemyNetwork::PolygonAmoy,
+        AlchemyNetwork::ArbitrumMainnet,
+        AlchemyNetwork::ArbitrumSepolia,
+        AlchemyNetwork::OptimismMainnet,
+        AlchemyNetwork::OptimismSepolia,
+        AlchemyNetwork::BaseMainnet,
+        AlchemyNetwork::BaseSepolia,
+        AlchemyNetwork::ZksyncMainnet,
+        AlchemyNetwork::ZksyncSepolia,
+    ];
+
+    /// The network's name in alchemy endpoint hosts, eg `eth-mainnet`
+    pub fn slug(&self) -> &'static str {
+        match self {
+            AlchemyNetwork::EthMainnet => "eth-mainnet",
+            AlchemyNetwork::EthGoerli => "eth-goerli",
+            AlchemyNetwork::EthSepolia => "eth-sepolia",
+            AlchemyNetwork::EthHolesky => "eth-holesky",
+            AlchemyNetwork::PolygonMainnet => "polygon-mainnet",
+            AlchemyNetwork::PolygonMumbai => "polygon-mumbai",
+            AlchemyNetwork::PolygonAmoy => "polygon-amoy",
+            AlchemyNetwork::ArbitrumMainnet => "arb-mainnet",
+            AlchemyNetwork::ArbitrumSepolia => "arb-sepolia",
+            AlchemyNetwork::OptimismMainnet => "opt-mainnet",
+            AlchemyNetwork::OptimismSepolia => "opt-sepolia",
+            AlchemyNetwork::BaseMainnet => "base-mainnet",
+            AlchemyNetwork::BaseSepolia => "base-sepolia",
+            AlchemyNetwork::ZksyncMainnet => "zksync-mainnet",
+            AlchemyNetwork::ZksyncSepolia => "zksync-sepolia",
+        }
+    }
+
+    /// The network's chain id
+    pub fn chain_id(&self) -> u64 {
+        match self {
+            AlchemyNetwork::EthMainnet => 1,
+            AlchemyNetwork::EthGoerli => 5,
+            AlchemyNetwork::EthSepolia => 11155111,
+            AlchemyNetwork::EthHolesky => 17000,
+            AlchemyNetwork::PolygonMainnet => 137,
+            AlchemyNetwork::PolygonMumbai => 80001,
+            AlchemyNetwork::PolygonAmoy => 80002,
+            AlchemyNetwork::ArbitrumMainnet => 42161,
+            AlchemyNetwork::ArbitrumSepolia => 421614,
+            AlchemyNetwork::OptimismMainnet => 10,
+            AlchemyNetwork::OptimismSepolia => 11155420,
+            AlchemyNetwork::BaseMainnet => 8453,
+            AlchemyNetwork::BaseSepolia => 84532,
+            AlchemyNetwork::ZksyncMainnet => 324,
+            AlchemyNetwork::ZksyncSepolia => 300,
+        }
+    }
+
+    /// The network with the given chain id
+    pub fn from_chain_id(chain_id: u64) -> Option<Self> {
+        Self::ALL.into_iter().find(|n| n.chain_id() == chain_id)
+    }
+
+    /// The host of the network's endpoints, shared by its https and wss endpoints
+    pub fn host(&self) -> String {
+        format!("{}.g.alchemy.com", self.slug())
+    }
+
+    /// The network's websocket endpoint for the api key
+    pub fn ws_url(&self, api_key: &str) -> String {
+        format!("wss://{}/v2/{}", self.host(), api_key)
+    }
+
+    /// The network's http endpoint for the api key
+    pub fn http_url(&self, api_key: &str) -> String {
+        format!("https://{}/v2/{}", self.host(), api_key)
+    }
+}
+
+impl fmt::Display for AlchemyNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.slug())
+    }
+}
+
+impl FromStr for AlchemyNetwork {
+    type Err = AlchemyConnectionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|n| n.slug() == s)
+            .ok_or_else(|| AlchemyConnectionError::UnknownNetwork(s.to_string()))
+    }
+}
+
+impl TryFrom<Chain> for AlchemyNetwork {
+    type Error = AlchemyConnectionError;
+
+    fn try_from(chain: Chain) -> Result<Self, Self::Error> {
+        let chain_id = u64::from(chain);
+        Self::from_chain_id(chain_id)
+            .ok_or_else(|| AlchemyConnectionError::UnknownNetwork(chain.to_string()))
+    }
+}
+
+impl TryFrom<AlchemyNetwork> for Chain {
+    type Error = AlchemyConnectionError;
+
+    /// Fails for networks ethers-rs has no [Chain](ethers::types::Chain) for
+    fn try_from(network: AlchemyNetwork) -> Result<Self, Self::Error> {
+        Chain::try_from(network.chain_id())
+            .map_err(|_| AlchemyConnectionError::UnknownNetwork(network.to_string()))
+    }
+}
+
+/// An Alchemy api or enhanced subscription that isn't served on every network
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    /// The `alchemy_pendingTransactions` subscription
+    PendingTransactions,
+    /// The `alchemy_minedTransactions` subscription
+    MinedTransactions,
+    /// The `trace_*` namespace
+    TraceApi,
+    /// The `debug_*` namespace
+    DebugApi,
+    /// The NFT API
+    NftApi,
+}
+
+/// The json-rpc methods of the NFT API
+const NFT_METHODS: [&str; 6] = [
+    "getNFTs",
+    "getNFTMetadata",
+    "getNFTsForCollection",
+    "getOwnersForToken",
+    "getOwnersForCollection",
+    "getContractMetadata",
+];
+
+impl Capability {
+    /// The capability an `eth_subscribe` requires, from its params
+    pub fn for_subscription(params: &[serde_json::Value]) -> Option<Self> {
+        match params.first().and_then(|p| p.as_str()) {
+            Some("alchemy_pendingTransactions") => Some(Capability::PendingTransactions),
+            Some("alchemy_minedTransactions") => Some(Capability::MinedTransactions),
+            _ => None,
+        }
+    }
+
+    /// The capability a json-rpc method requires
+    pub fn for_method(method: &str) -> Option<Self> {
+        if method.starts_with("trace_") {
+            Some(Capability::TraceApi)
+        } else if method.starts_with("debug_") {
+            Some(Capability::DebugApi)
+        } else if NFT_METHODS.contains(&method) {
+            Some(Capability::NftApi)
+        } else {
+            None
+        }
+    }
+}
+
+impl AlchemyNetwork {
+    /// Whether the network serves the capability
+    ///
+    /// Mirrors Alchemy's published chain support, which may lag behind newly enabled networks.
+    pub fn supports(&self, capability: Capability) -> bool {
+        use AlchemyNetwork::*;
+
+        match capability {
+            Capability::PendingTransactions => matches!(
+                self,
+                EthMainnet | EthGoerli | EthSepolia | PolygonMainnet | PolygonMumbai | PolygonAmoy
+            ),
+            Capability::MinedTransactions => !matches!(self, ZksyncMainnet | ZksyncSepolia),
+            Capability::TraceApi => matches!(
+                self,
+                EthMainnet | EthGoerli | EthSepolia | EthHolesky | BaseMainnet | BaseSepolia
+            ),
+            Capability::DebugApi => !matches!(
+                self,
+                EthGoerli | PolygonMumbai | ZksyncMainnet | ZksyncSepolia
+            ),
+            Capability::NftApi => !matches!(
+                self,
+                EthGoerli | ZksyncMainnet | ZksyncSepolia | ArbitrumSepolia | OptimismSepolia
+            ),
+        }
+    }
+
+    /// Fail with an [UnsupportedOnNetwork](AlchemyConnectionError::UnsupportedOnNetwork) error if the network doesn't serve the capability
+    pub fn require(&self, capability: Capability) -> Result<(), AlchemyConnectionError> {
+        if self.supports(capability) {
+            Ok(())
+        } else {
+            Err(AlchemyConnectionError::UnsupportedOnNetwork(
+                capability, *self,
+            ))
+        }
+    }
+}
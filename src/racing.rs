@@ -0,0 +1,272 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    pin::Pin,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use ethers::types::{Transaction, TxHash};
+use futures::{stream::SelectAll, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+
+use crate::connectors::prelude::*;
+use crate::subscription::SubscriptionStream;
+
+/// The default number of recently seen keys a [RacingStream] dedupes against
+pub const DEFAULT_DEDUPE_CAPACITY: usize = 16 * 1024;
+
+/// An item raced across several feeds, deduplicated by its key
+pub trait Raced {
+    /// The key identifying the same item on every feed
+    type Key: Hash + Eq + Clone + Unpin;
+
+    /// The item's key
+    fn key(&self) -> Self::Key;
+}
+
+impl Raced for Transaction {
+    type Key = TxHash;
+
+    fn key(&self) -> TxHash {
+        self.hash
+    }
+}
+
+impl Raced for TxHash {
+    type Key = TxHash;
+
+    fn key(&self) -> TxHash {
+        *self
+    }
+}
+
+/// An item delivered by the feed that saw it first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RacedItem<T> {
+    /// The item
+    pub value: T,
+    /// The index of the winning feed
+    pub feed: usize,
+}
+
+/// How a feed fares in a race
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeedStats {
+    /// The number of items the feed delivered first
+    pub wins: u64,
+    /// The number of items the feed delivered after another feed
+    pub losses: u64,
+    /// The total time the feed's lost items arrived after the winner's
+    pub total_lag: Duration,
+}
+
+impl FeedStats {
+    /// The mean time the feed's lost items arrived after the winner's, `None` without losses
+    pub fn mean_lag(&self) -> Option<Duration> {
+        match self.losses {
+            0 => None,
+            losses => Some(self.total_lag.div_f64(losses as f64)),
+        }
+    }
+}
+
+/// A bounded cache of recently seen keys and when they were first seen
+///
+/// Once full, the least recently seen key is forgotten. Keys seen again are refreshed lazily:
+/// their stale position in the queue is skipped on eviction, and the queue is compacted once it
+/// grows past twice the capacity.
+#[derive(Debug)]
+pub struct SeenCache<K> {
+    /// The maximum number of keys remembered
+    capacity: usize,
+    /// The recency stamp and first sighting of every remembered key
+    entries: HashMap<K, (u64, Instant)>,
+    /// The keys in the order they were seen, along with their recency stamp at the time
+    order: VecDeque<(K, u64)>,
+    /// The next recency stamp
+    next_stamp: u64,
+}
+
+impl<K: Hash + Eq + Clone> SeenCache<K> {
+    /// Create a new cache remembering up to `capacity` keys
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+            next_stamp: 0,
+        }
+    }
+
+    /// The number of remembered keys
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no key is remembered
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Mark the key as seen at `now`, returning when it was first seen if it is remembered
+    pub fn see(&mut self, key: K, now: Instant) -> Option<Instant> {
+        let stamp = self.next_stamp;
+        self.next_stamp += 1;
+        let first_seen = self.entries.get(&key).map(|(_, first_seen)| *first_seen);
+        self.entries
+            .insert(key.clone(), (stamp, first_seen.unwrap_or(now)));
+        self.order.push_back((key, stamp));
+
+        while self.entries.len() > self.capacity {
+            if let Some((key, stamp)) = self.order.pop_front() {
+                if self.entries.get(&key).map(|(s, _)| *s) == Some(stamp) {
+                    self.entries.remove(&key);
+                }
+            }
+        }
+        if self.order.len() > 2 * self.capacity {
+            let entries = &self.entries;
+            self.order
+                .retain(|(key, stamp)| entries.get(key).map(|(s, _)| *s) == Some(*stamp));
+        }
+
+        first_seen
+    }
+}
+
+/// The item stream of a feed, tagged with the feed's index
+type Feed<T> = Pin<Box<dyn Stream<Item = (usize, Result<T, AlchemyConnectionError>)> + Send>>;
+
+/// Races the same subscription on several feeds, delivering every item once, from whichever feed saw it first
+///
+/// Items are deduplicated by their [key](Raced::key), eg the transaction hash, against the most recently
+/// seen keys (see [with_capacity](RacingStream::with_capacity)). Every win and loss is recorded in the
+/// [stats](RacingStream::stats) of its feed, along with how far the losing feeds lag behind the winner.
+///
+/// Feeds are indexed by the connection or endpoint they run on (see [from_indexed](RacingStream::from_indexed)),
+/// so a [RacedItem]'s feed and the [stats](RacingStream::stats) index match the connector's own indexes even when
+/// some connections got no feed.
+///
+/// Errors of any feed are passed through, and the stream ends once every feed has ended.
+///
+/// ## Example
+///
+/// ```rust
+/// use alchemy_rs::{connectors::AlchemyConnectorType, prelude::*};
+/// use futures::StreamExt;
+///
+/// async {
+///     let mut manager = AlchemyManager::builder()
+///         .with_api_key("<API_KEY>")
+///         .with_connector_type(AlchemyConnectorType::Pool)
+///         .with_pool_size(3)
+///         .build()
+///         .unwrap();
+///     manager.connect().await.unwrap();
+///
+///     let mut race = manager.race_pending_transactions(None, None).await.unwrap();
+///     while let Some(Ok(raced)) = race.next().await {
+///         println!("Feed {} won {:?}", raced.feed, raced.value.hash);
+///     }
+///     println!("Feed stats: {:?}", race.stats());
+/// };
+/// ```
+pub struct RacingStream<T: Raced> {
+    /// The merged feeds
+    feeds: SelectAll<Feed<T>>,
+    /// The recently seen keys
+    seen: SeenCache<T::Key>,
+    /// The index of every feed
+    indexes: Vec<usize>,
+    /// The stats of every index, up to the highest feed index
+    stats: Vec<FeedStats>,
+}
+
+impl<T: Raced> std::fmt::Debug for RacingStream<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RacingStream")
+            .field("feeds", &self.feeds.len())
+            .field("seen", &self.seen.len())
+            .field("indexes", &self.indexes)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+impl<T: Raced + DeserializeOwned + Send + 'static> RacingStream<T> {
+    /// Race the subscription streams, indexing the feeds in order
+    pub fn new(streams: Vec<SubscriptionStream<T>>) -> Self {
+        Self::from_indexed(streams.into_iter().enumerate().collect())
+    }
+
+    /// Race the subscription streams, each under the given index, eg of the connection it runs on
+    ///
+    /// Indexes without a stream keep default [stats](RacingStream::stats).
+    pub fn from_indexed(streams: Vec<(usize, SubscriptionStream<T>)>) -> Self {
+        let indexes: Vec<usize> = streams.iter().map(|(index, _)| *index).collect();
+        let len = indexes.iter().max().map_or(0, |index| index + 1);
+        let feeds = streams.into_iter().map(|(index, stream)| {
+            let feed: Feed<T> = Box::pin(stream.map(move |item| (index, item)));
+            feed
+        });
+        Self {
+            feeds: futures::stream::select_all(feeds),
+            seen: SeenCache::new(DEFAULT_DEDUPE_CAPACITY),
+            indexes,
+            stats: vec![FeedStats::default(); len],
+        }
+    }
+}
+
+impl<T: Raced> RacingStream<T> {
+    /// Set the number of recently seen keys to dedupe against, forgetting the keys seen so far
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.seen = SeenCache::new(capacity);
+        self
+    }
+
+    /// The number of raced feeds
+    pub fn feeds(&self) -> usize {
+        self.indexes.len()
+    }
+
+    /// The index of every raced feed
+    pub fn indexes(&self) -> &[usize] {
+        &self.indexes
+    }
+
+    /// The stats of every feed, by index
+    pub fn stats(&self) -> &[FeedStats] {
+        &self.stats
+    }
+}
+
+impl<T: Raced> Stream for RacingStream<T> {
+    type Item = Result<RacedItem<T>, AlchemyConnectionError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            let (feed, value) = match this.feeds.poll_next_unpin(cx) {
+                Poll::Ready(Some((feed, Ok(value)))) => (feed, value),
+                Poll::Ready(Some((_, Err(e)))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            let now = Instant::now();
+            let stats = &mut this.stats[feed];
+            match this.seen.see(value.key(), now) {
+                Some(first_seen) => {
+                    stats.losses += 1;
+                    stats.total_lag += now.saturating_duration_since(first_seen);
+                }
+                None => {
+                    stats.wins += 1;
+                    return Poll::Ready(Some(Ok(RacedItem { value, feed })));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+use serde::de::DeserializeOwned;
+use tokio::sync::mpsc;
+
+use crate::connectors::prelude::*;
+use crate::messages::outbound::AlchemySocketMessage;
+
+/// The json-rpc id of the `eth_unsubscribe` requests sent when a [SubscriptionStream] is dropped
+pub const UNSUBSCRIBE_ON_DROP_REQUEST_ID: u64 = u64::MAX;
+
+/// A stream of the notifications of a single subscription
+///
+/// Each item is the `result` of an `eth_subscription` notification, deserialized into `T`.
+/// The stream ends once the connection is given up on.
+///
+/// The stream is independent of the [AlchemyManager](crate::manager::AlchemyManager) it was
+/// created by, so it can be combined with [StreamExt](futures::StreamExt) combinators or handed to
+/// another task. Dropping the stream releases its route, on whichever connection it is routed by
+/// at the time, and queues a best-effort `eth_unsubscribe` there.
+#[derive(Debug)]
+pub struct SubscriptionStream<T> {
+    /// The subscription id
+    id: uuid::Uuid,
+    /// The channel notifications are routed to
+    receiver: mpsc::Receiver<serde_json::Value>,
+    /// The registry the subscription is bound in, released on drop
+    registry: Arc<RouteRegistry>,
+    /// The notification result type
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T> SubscriptionStream<T> {
+    /// Create a new stream over the notifications routed to the receiver
+    ///
+    /// Subscriptions that aren't bound in the registry are left alone on drop, eg provider
+    /// subscriptions which unsubscribe once the receiver is dropped.
+    pub fn new(
+        id: uuid::Uuid,
+        receiver: mpsc::Receiver<serde_json::Value>,
+        registry: Arc<RouteRegistry>,
+    ) -> Self {
+        Self {
+            id,
+            receiver,
+            registry,
+            _item: PhantomData,
+        }
+    }
+
+    /// The subscription id
+    pub fn id(&self) -> uuid::Uuid {
+        self.id
+    }
+}
+
+impl<T: DeserializeOwned> Stream for SubscriptionStream<T> {
+    type Item = Result<T, AlchemyConnectionError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.receiver.poll_recv(cx) {
+            Poll::Ready(Some(mut notification)) => {
+                let result = notification
+                    .get_mut("params")
+                    .and_then(|p| p.get_mut("result"))
+                    .map(serde_json::Value::take)
+                    .unwrap_or_default();
+                Poll::Ready(Some(
+                    serde_json::from_value(result).map_err(AlchemyConnectionError::Deserialization),
+                ))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<T> Drop for SubscriptionStream<T> {
+    fn drop(&mut self) {
+        if let Some(binding) = self.registry.unbind(self.id) {
+            release(self.id, &binding);
+        }
+    }
+}
+
+/// Remove the subscription's route from the connection, queueing an `eth_unsubscribe` if it was still routed
+pub(crate) fn release(id: uuid::Uuid, binding: &RouteBinding) {
+    // Already unsubscribed, or the connection was given up on
+    let server_id = match lock(&binding.router).remove(id) {
+        Some(server_id) => server_id,
+        None => return,
+    };
+
+    let message = AlchemySocketMessage::unsubscribe(UNSUBSCRIBE_ON_DROP_REQUEST_ID, server_id);
+    match serde_json::to_string(&message) {
+        Ok(text) => {
+            if binding.outbound.send(text).is_err() {
+                tracing::debug!("Connection closed, not unsubscribing {}", id);
+            }
+        }
+        Err(e) => tracing::warn!("Failed to serialize unsubscribe message: {:?}", e),
+    }
+}
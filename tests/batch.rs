@@ -0,0 +1,150 @@
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+
+mod util;
+
+/// Answers every json-rpc request with its method name, and `eth_fail` with an error
+fn answer(request: &serde_json::Value) -> serde_json::Value {
+    match request["method"].as_str() {
+        Some("eth_fail") => serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "error": { "code": -32601, "message": "method not found" },
+        }),
+        method => serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": method,
+        }),
+    }
+}
+
+fn batch() -> JsonRpcBatch {
+    let mut batch = JsonRpcBatch::new();
+    batch.push("eth_blockNumber", ()).unwrap();
+    batch.push("eth_fail", ["0x1234"]).unwrap();
+    batch.push("eth_chainId", ()).unwrap();
+    batch
+}
+
+fn assert_results(results: Vec<Result<serde_json::Value, JsonRpcError>>) {
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap(), "eth_blockNumber");
+    assert_eq!(results[1].as_ref().unwrap_err().code, -32601);
+    assert_eq!(results[2].as_ref().unwrap(), "eth_chainId");
+}
+
+#[actix_rt::test]
+async fn test_websocket_batch() {
+    // The batch arrives in a single frame, and is answered in reverse order
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        let requests = socket.recv_json().await.unwrap();
+        let requests = requests.as_array().unwrap();
+        let methods = requests
+            .iter()
+            .map(|r| r["method"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(methods, ["eth_blockNumber", "eth_fail", "eth_chainId"]);
+
+        let responses = requests.iter().rev().map(answer).collect::<Vec<_>>();
+        socket.send_json(serde_json::Value::Array(responses)).await;
+
+        // A single request follows the batch
+        let request = socket.recv_json().await.unwrap();
+        socket.send_json(answer(&request)).await;
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None);
+    manager.connect().await.unwrap();
+    let client = manager.client().unwrap();
+
+    // Nothing is sent for an empty batch
+    assert!(client.batch(&JsonRpcBatch::new()).await.unwrap().is_empty());
+
+    assert_results(manager.batch(&batch()).await.unwrap());
+    let method: String = client.request("eth_gasPrice", ()).await.unwrap();
+    assert_eq!(method, "eth_gasPrice");
+}
+
+#[actix_rt::test]
+async fn test_websocket_batch_times_out_on_partial_response() {
+    // Only the first call of the batch is answered
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        let requests = socket.recv_json().await.unwrap();
+        let responses = vec![answer(&requests[0])];
+        socket.send_json(serde_json::Value::Array(responses)).await;
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::builder()
+        .with_url(&format!("ws://{}", addr))
+        .with_request_timeout(std::time::Duration::from_millis(200))
+        .build()
+        .unwrap();
+    manager.connect().await.unwrap();
+
+    match manager.batch(&batch()).await {
+        Err(AlchemyConnectionError::RequestTimeout(_)) => (),
+        other => panic!("Expected a timeout, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_websocket_batch_rejections() {
+    // The first batch is rejected as a whole, the second one element by element, and the third one answered
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        let invalid = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": null,
+            "error": { "code": -32600, "message": "invalid request" },
+        });
+        socket.recv_json().await.unwrap();
+        socket.send_json(invalid.clone()).await;
+
+        let requests = socket.recv_json().await.unwrap();
+        let responses = vec![answer(&requests[0]), invalid, answer(&requests[2])];
+        socket.send_json(serde_json::Value::Array(responses)).await;
+
+        let requests = socket.recv_json().await.unwrap();
+        let responses = requests.as_array().unwrap().iter().map(answer).collect();
+        socket.send_json(serde_json::Value::Array(responses)).await;
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None);
+    manager.connect().await.unwrap();
+
+    // The rejections fail the batches right away, well before the request timeout
+    let batch = batch();
+    for _ in 0..2 {
+        let rejected =
+            tokio::time::timeout(std::time::Duration::from_secs(5), manager.batch(&batch));
+        match rejected.await.unwrap() {
+            Err(AlchemyConnectionError::RpcError(e)) => assert_eq!(e.code, -32600),
+            other => panic!("Expected a json-rpc error, got: {:?}", other),
+        }
+    }
+    assert_results(manager.batch(&batch).await.unwrap());
+}
+
+#[actix_rt::test]
+async fn test_manager_http_batch() {
+    let addr = util::spawn_http_server(|request| {
+        let requests = request.body.as_array().unwrap();
+        let responses = requests.iter().rev().map(answer).collect::<Vec<_>>();
+        (200, serde_json::Value::Array(responses))
+    })
+    .await;
+
+    let mut manager = AlchemyManager::builder()
+        .with_url(&format!("http://{}", addr))
+        .with_connector_type(AlchemyConnectorType::Http)
+        .build()
+        .unwrap();
+    manager.connect().await.unwrap();
+
+    assert_results(manager.batch(&batch()).await.unwrap());
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+use tokio::net::TcpListener;
+
+#[test]
+fn test_builder_formats_the_endpoint() {
+    let manager = AlchemyManager::builder()
+        .with_api_key("some_api-key")
+        .with_network(AlchemyNetwork::PolygonMainnet)
+        .with_request_timeout(Duration::from_secs(5))
+        .with_header("X-Alchemy-Client", "alchemy-rs")
+        .build()
+        .unwrap();
+    assert_eq!(
+        manager.url,
+        "wss://polygon-mainnet.g.alchemy.com/v2/some_api-key"
+    );
+    assert_eq!(manager.request_timeout, Duration::from_secs(5));
+    assert_eq!(manager.headers.len(), 1);
+
+    // The network defaults to ethereum mainnet
+    let manager = AlchemyManager::builder()
+        .with_api_key("key")
+        .build()
+        .unwrap();
+    assert_eq!(manager.url, "wss://eth-mainnet.g.alchemy.com/v2/key");
+    assert_eq!(manager.network, Some(AlchemyNetwork::EthMainnet));
+}
+
+#[test]
+fn test_builder_validates_the_configuration() {
+    let invalid = [
+        AlchemyManager::builder(),
+        AlchemyManager::builder()
+            .with_api_key("key")
+            .with_url("ws://localhost:8546"),
+        AlchemyManager::builder().with_api_key("not a key"),
+        AlchemyManager::builder().with_url("http://localhost:8546"),
+        AlchemyManager::builder()
+            .with_api_key("key")
+            .with_request_timeout(Duration::ZERO),
+        AlchemyManager::builder()
+            .with_api_key("key")
+            .with_notification_buffer_size(0),
+        AlchemyManager::builder()
+            .with_api_key("key")
+            .with_header("Sec-WebSocket-Key", "abc"),
+        AlchemyManager::builder()
+            .with_api_key("key")
+            .with_header("X-Client", "alchemy-rs")
+            .with_connector_type(AlchemyConnectorType::Provider),
+    ];
+    for builder in invalid {
+        assert!(builder.build().is_err());
+    }
+}
+
+#[actix_rt::test]
+async fn test_connect_timeout() {
+    // Accept the connection, but never answer the handshake
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _socket = listener.accept().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    });
+
+    let mut manager = AlchemyManager::builder()
+        .with_url(&format!("ws://{}", addr))
+        .with_connect_timeout(Duration::from_millis(100))
+        .build()
+        .unwrap();
+    match manager.connect().await {
+        Err(AlchemyConnectionError::ConnectTimeout(_)) => (),
+        other => panic!("Expected a connect timeout, got: {:?}", other),
+    }
+}
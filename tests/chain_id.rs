@@ -0,0 +1,68 @@
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+
+mod util;
+
+/// Spawns a server answering `eth_chainId` with polygon's chain id
+async fn spawn_polygon_server() -> std::net::SocketAddr {
+    util::spawn_ws_server(|_, mut socket| async move {
+        let request = socket.recv_json().await.unwrap();
+        assert_eq!(request["method"], "eth_chainId");
+        socket
+            .respond(&request, serde_json::Value::String("0x89".to_string()))
+            .await;
+        socket.drain().await;
+    })
+    .await
+}
+
+#[actix_rt::test]
+async fn test_chain_id_verification() {
+    let addr = spawn_polygon_server().await;
+    let mut manager = AlchemyManager::builder()
+        .with_url(&format!("ws://{}", addr))
+        .with_network(AlchemyNetwork::PolygonMainnet)
+        .with_chain_id_verification(true)
+        .build()
+        .unwrap();
+    manager.connect().await.unwrap();
+
+    // The verified chain id is cached, as the server only answers once
+    assert_eq!(manager.chain_id().await.unwrap(), 137);
+}
+
+#[actix_rt::test]
+async fn test_chain_id_without_verification() {
+    let addr = spawn_polygon_server().await;
+    let mut manager = AlchemyManager::builder()
+        .with_url(&format!("ws://{}", addr))
+        .with_request_timeout(std::time::Duration::from_secs(1))
+        .build()
+        .unwrap();
+    manager.connect().await.unwrap();
+
+    // Fetched on first use, then cached
+    assert_eq!(manager.chain_id().await.unwrap(), 137);
+    assert_eq!(manager.chain_id().await.unwrap(), 137);
+}
+
+#[actix_rt::test]
+async fn test_chain_id_mismatch() {
+    let addr = spawn_polygon_server().await;
+    let mut manager = AlchemyManager::builder()
+        .with_url(&format!("ws://{}", addr))
+        .with_network(AlchemyNetwork::EthMainnet)
+        .with_chain_id_verification(true)
+        .build()
+        .unwrap();
+    match manager.connect().await {
+        Err(AlchemyConnectionError::ChainIdMismatch(1, 137)) => (),
+        other => panic!("Expected a chain id mismatch, got: {:?}", other),
+    }
+
+    // The mismatched connection is dropped
+    match manager.chain_id().await {
+        Err(AlchemyConnectionError::MissingConnection) => (),
+        other => panic!("Expected a missing connection, got: {:?}", other),
+    }
+    assert!(matches!(manager.connector, AlchemyConnector::Raw(None)));
+}
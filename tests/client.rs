@@ -0,0 +1,64 @@
+use alchemy_rs::prelude::*;
+use futures::StreamExt;
+
+mod util;
+
+#[test]
+fn test_client_is_shareable() {
+    fn assert_shareable<T: Clone + Send + Sync + 'static>() {}
+    assert_shareable::<AlchemyClient>();
+}
+
+#[actix_rt::test]
+async fn test_concurrent_requests_and_subscriptions() {
+    // Hold back every request until all of them arrived, then answer in reverse order
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        let mut requests = vec![];
+        for _ in 0..5 {
+            requests.push(socket.recv_json().await.unwrap());
+        }
+        for request in requests.iter().rev() {
+            let result = match request["method"].as_str().unwrap() {
+                "eth_subscribe" => serde_json::Value::String(util::subscription_hex(1)),
+                _ => request["params"][0].clone(),
+            };
+            socket.respond(request, result).await;
+        }
+        socket
+            .send_json(util::pending_transaction_notification(
+                &util::subscription_hex(1),
+            ))
+            .await;
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None);
+    manager.connect().await.unwrap();
+    let client = manager.client().unwrap();
+
+    // Every task gets the response to its own request
+    let requests = (0..4u64).map(|n| {
+        let client = client.clone();
+        tokio::spawn(async move {
+            let echoed: U64 = client.request("eth_echo", [U64::from(n)]).await.unwrap();
+            assert_eq!(echoed, U64::from(n));
+        })
+    });
+    let requests: Vec<_> = requests.collect();
+
+    let subscriber = client.clone();
+    let mut stream = tokio::spawn(async move {
+        subscriber
+            .subscribe_pending_transactions(None, None)
+            .await
+            .unwrap()
+    })
+    .await
+    .unwrap();
+
+    for request in requests {
+        request.await.unwrap();
+    }
+    assert!(stream.next().await.unwrap().is_ok());
+}
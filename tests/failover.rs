@@ -0,0 +1,205 @@
+use std::{sync::Arc, time::Duration};
+
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+use futures::StreamExt;
+use tokio::sync::{mpsc, Notify};
+
+mod util;
+
+/// Spawns a server confirming subscriptions with a notification and answering requests with `answer`
+///
+/// The first connection is closed once `kill` is notified, later connections are closed right away.
+/// Returns the address along with the methods of the requests the first connection received.
+async fn spawn_server(
+    sub_id: u128,
+    answer: &'static str,
+    kill: Arc<Notify>,
+) -> (std::net::SocketAddr, mpsc::UnboundedReceiver<String>) {
+    let (methods, methods_rx) = mpsc::unbounded_channel();
+    let addr = util::spawn_ws_server(move |index, mut socket| {
+        let kill = Arc::clone(&kill);
+        let methods = methods.clone();
+        async move {
+            if index > 0 {
+                return;
+            }
+            loop {
+                let request = tokio::select! {
+                    request = socket.recv_json() => match request {
+                        Some(request) => request,
+                        None => return,
+                    },
+                    _ = kill.notified() => return,
+                };
+                let _ = methods.send(request["method"].as_str().unwrap_or_default().to_string());
+                if request["method"] == "eth_subscribe" {
+                    let sub_id = util::subscription_hex(sub_id);
+                    socket
+                        .respond(&request, serde_json::Value::String(sub_id.clone()))
+                        .await;
+                    socket
+                        .send_json(util::pending_transaction_notification(&sub_id))
+                        .await;
+                } else {
+                    socket.respond(&request, answer.into()).await;
+                }
+            }
+        }
+    })
+    .await;
+    (addr, methods_rx)
+}
+
+#[actix_rt::test]
+async fn test_failover_moves_subscriptions_to_the_backup() {
+    let kill_primary = Arc::new(Notify::new());
+    let (primary, _) = spawn_server(1, "0x1", Arc::clone(&kill_primary)).await;
+    let (backup, mut backup_methods) = spawn_server(7, "0x2", Arc::new(Notify::new())).await;
+
+    let mut manager = AlchemyManager::builder()
+        .with_connector_type(AlchemyConnectorType::Failover)
+        .with_url(&format!("ws://{}/v2/primary-key", primary))
+        .with_backup_url(&format!("ws://{}/v2/backup-key", backup))
+        .with_failover_config(FailoverConfig {
+            probe_interval: Duration::from_millis(50),
+            probe_timeout: Duration::from_millis(200),
+            max_failures: 2,
+        })
+        .with_reconnect_policy(ReconnectPolicy {
+            initial_backoff: Duration::from_millis(10),
+            jitter: 0.0,
+            ..ReconnectPolicy::default()
+        })
+        .build()
+        .unwrap();
+    manager.connect().await.unwrap();
+    let mut events = manager.failover_events().unwrap();
+
+    // Subscriptions start out on the primary
+    let mut stream = manager
+        .subscribe_pending_transactions(None, None)
+        .await
+        .unwrap();
+    stream.next().await.unwrap().unwrap();
+
+    kill_primary.notify_one();
+    let event = tokio::time::timeout(Duration::from_secs(5), events.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    // The api keys in the urls are left out of the event
+    assert_eq!(
+        event,
+        FailoverEvent {
+            from: format!("ws://{}", primary),
+            to: format!("ws://{}", backup),
+            subscriptions: 1,
+        }
+    );
+
+    // The subscription keeps its stream on the backup, which now serves every request
+    stream.next().await.unwrap().unwrap();
+    let answer: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(answer, U64::from(2));
+    match &manager.connector {
+        AlchemyConnector::Failover(Some(failover)) => {
+            assert_eq!(failover.active(), 1);
+            let health = failover.health();
+            assert!(!health[0].alive);
+            assert!(health[1].alive);
+            assert!(health[1].latency.is_some());
+        }
+        other => panic!("Expected a connected failover, got: {:?}", other),
+    }
+
+    // Dropping the stream unsubscribes on the backup it was moved to
+    drop(stream);
+    let unsubscribed = async {
+        while let Some(method) = backup_methods.recv().await {
+            if method == "eth_unsubscribe" {
+                return;
+            }
+        }
+        panic!("The backup never received an eth_unsubscribe");
+    };
+    tokio::time::timeout(Duration::from_secs(5), unsubscribed)
+        .await
+        .unwrap();
+}
+
+#[actix_rt::test]
+async fn test_failover_connects_without_the_primary() {
+    let (backup, _) = spawn_server(7, "0x2", Arc::new(Notify::new())).await;
+
+    // Nothing listens on the primary
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let primary = listener.local_addr().unwrap();
+    drop(listener);
+
+    let mut manager = AlchemyManager::new(
+        &format!("ws://{}", primary),
+        Some(AlchemyConnectorType::Failover),
+    )
+    .with_backup_url(&format!("ws://{}", backup));
+    manager.connect().await.unwrap();
+
+    let answer: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(answer, U64::from(2));
+    let mut stream = manager
+        .subscribe_pending_transactions(None, None)
+        .await
+        .unwrap();
+    stream.next().await.unwrap().unwrap();
+}
+
+#[test]
+fn test_endpoint_health_scoring() {
+    let mut fast = EndpointHealth::default();
+    fast.record_success(Duration::from_millis(10));
+    let mut slow = EndpointHealth::default();
+    slow.record_success(Duration::from_millis(100));
+    assert!(fast.score().unwrap() < slow.score().unwrap());
+
+    // Errors outweigh a small latency advantage, and enough of them in a row kill the endpoint
+    fast.record_failure(3);
+    assert!(fast.alive);
+    assert!(fast.score().unwrap() > slow.score().unwrap());
+    fast.record_failure(3);
+    fast.record_failure(3);
+    assert!(!fast.alive);
+    assert_eq!(fast.score(), None);
+
+    // A response revives it
+    fast.record_success(Duration::from_millis(10));
+    assert!(fast.alive);
+    assert_eq!(fast.consecutive_failures, 0);
+}
+
+#[test]
+fn test_failover_configuration_is_validated() {
+    let builders = [
+        // A failover without a backup
+        AlchemyManager::builder()
+            .with_connector_type(AlchemyConnectorType::Failover)
+            .with_url("ws://localhost"),
+        // A backup without a failover
+        AlchemyManager::builder()
+            .with_url("ws://localhost")
+            .with_backup_url("ws://localhost:8546"),
+        // Probing without an interval
+        AlchemyManager::builder()
+            .with_connector_type(AlchemyConnectorType::Failover)
+            .with_url("ws://localhost")
+            .with_backup_url("ws://localhost:8546")
+            .with_failover_config(FailoverConfig {
+                probe_interval: Duration::ZERO,
+                ..FailoverConfig::default()
+            }),
+    ];
+    for builder in builders {
+        match builder.build() {
+            Err(AlchemyConnectionError::InvalidConfig(_)) => (),
+            other => panic!("Expected an invalid config, got: {:?}", other),
+        }
+    }
+}
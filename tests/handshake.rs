@@ -0,0 +1,190 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use alchemy_rs::connectors::prelude::*;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+/// Reads a raw http request from the socket, up to the end of its headers
+async fn read_request(socket: &mut TcpStream) {
+    let mut request = vec![];
+    let mut buf = [0u8; 1024];
+    while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+        let n = socket.read(&mut buf).await.unwrap();
+        if n == 0 {
+            return;
+        }
+        request.extend_from_slice(&buf[..n]);
+    }
+}
+
+/// Spawns a stand-in server answering every handshake with the raw http response
+async fn spawn_status_server(response: String) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = listener.accept().await {
+            read_request(&mut socket).await;
+            socket.write_all(response.as_bytes()).await.unwrap();
+        }
+    });
+    addr
+}
+
+/// Answers every stream opened by the memory transport with the raw http response, regardless of the url's scheme
+fn spawn_memory_status_server(response: String) -> Arc<MemoryTransport> {
+    let (transport, mut listener) = MemoryTransport::new(4096);
+    tokio::spawn(async move {
+        while let Some(mut stream) = listener.accept().await {
+            let mut request = vec![];
+            let mut buf = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                match stream.read(&mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&buf[..n]),
+                }
+            }
+            let _ = stream.write_all(response.as_bytes()).await;
+        }
+    });
+    Arc::new(transport)
+}
+
+/// Spawns a stand-in server accepting a single websocket handshake
+async fn spawn_accepting_server() -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut server = soketto::handshake::Server::new(socket.compat());
+        let key = server.receive_request().await.unwrap().key();
+        let accept = soketto::handshake::server::Response::Accept {
+            key,
+            protocol: None,
+        };
+        server.send_response(&accept).await.unwrap();
+    });
+    addr
+}
+
+#[actix_rt::test]
+async fn test_handshake_unauthorized() {
+    let addr = spawn_status_server(String::from(
+        "HTTP/1.1 401 Unauthorized\r\nContent-Length: 0\r\n\r\n",
+    ))
+    .await;
+    let mut connection = RawAlchemyConnection::new();
+    match connection
+        .connect(&format!("ws://{}/v2/bad-key", addr))
+        .await
+    {
+        Err(AlchemyConnectionError::Unauthorized(401)) => (),
+        other => panic!("Expected an unauthorized error, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_handshake_rate_limited() {
+    let addr = spawn_status_server(String::from(
+        "HTTP/1.1 429 Too Many Requests\r\nContent-Length: 0\r\n\r\n",
+    ))
+    .await;
+    let mut connection = RawAlchemyConnection::new();
+    match connection.connect(&format!("ws://{}/v2/key", addr)).await {
+        Err(AlchemyConnectionError::RateLimited(None)) => (),
+        other => panic!("Expected a rate limited error, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_handshake_rejected() {
+    let addr = spawn_status_server(String::from(
+        "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\n\r\n",
+    ))
+    .await;
+    let mut connection = RawAlchemyConnection::new();
+    match connection.connect(&format!("ws://{}/v2/key", addr)).await {
+        Err(AlchemyConnectionError::HandshakeRejected(503)) => (),
+        other => panic!("Expected a rejected handshake error, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_handshake_follows_redirect() {
+    let target = spawn_accepting_server().await;
+    let addr = spawn_status_server(format!(
+        "HTTP/1.1 301 Moved Permanently\r\nLocation: ws://{}/v2/key\r\nContent-Length: 0\r\n\r\n",
+        target
+    ))
+    .await;
+    let mut connection = RawAlchemyConnection::new();
+    connection
+        .connect(&format!("ws://{}/v2/key", addr))
+        .await
+        .unwrap();
+    assert!(connection.is_connected());
+}
+
+#[actix_rt::test]
+async fn test_handshake_redirect_limit() {
+    // Redirect back to the same server, forever
+    let addr = spawn_status_server(String::from(
+        "HTTP/1.1 307 Temporary Redirect\r\nLocation: /v2/again\r\nContent-Length: 0\r\n\r\n",
+    ))
+    .await;
+    let mut connection = RawAlchemyConnection::new().with_max_redirects(2);
+    match connection.connect(&format!("ws://{}/v2/key", addr)).await {
+        Err(AlchemyConnectionError::TooManyRedirects(2)) => (),
+        other => panic!("Expected a too many redirects error, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_handshake_rejects_insecure_redirect() {
+    // The api key must not be sent in plaintext, so the downgrade isn't followed
+    let transport = spawn_memory_status_server(String::from(
+        "HTTP/1.1 301 Moved Permanently\r\nLocation: ws://eth-mainnet.g.alchemy.com/v2/key\r\nContent-Length: 0\r\n\r\n",
+    ));
+    let mut connection = RawAlchemyConnection::new().with_transport(transport);
+    match connection
+        .connect("wss://eth-mainnet.g.alchemy.com/v2/key")
+        .await
+    {
+        Err(AlchemyConnectionError::InsecureRedirect(url)) => {
+            assert_eq!(url, "ws://eth-mainnet.g.alchemy.com/v2/key")
+        }
+        other => panic!("Expected an insecure redirect error, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_handshake_rejects_cross_host_redirect() {
+    let transport = spawn_memory_status_server(String::from(
+        "HTTP/1.1 307 Temporary Redirect\r\nLocation: wss://example.com/v2/key\r\nContent-Length: 0\r\n\r\n",
+    ));
+    let mut connection = RawAlchemyConnection::new().with_transport(transport.clone());
+    match connection
+        .connect("wss://eth-mainnet.g.alchemy.com/v2/key")
+        .await
+    {
+        Err(AlchemyConnectionError::CrossHostRedirect(url)) => {
+            assert_eq!(url, "wss://example.com/v2/key")
+        }
+        other => panic!("Expected a cross-host redirect error, got: {:?}", other),
+    }
+
+    // Once allowed, the redirect is followed, here until the limit as the server always redirects
+    let mut connection = RawAlchemyConnection::new()
+        .with_transport(transport)
+        .with_cross_host_redirects(true)
+        .with_max_redirects(1);
+    match connection
+        .connect("wss://eth-mainnet.g.alchemy.com/v2/key")
+        .await
+    {
+        Err(AlchemyConnectionError::TooManyRedirects(1)) => (),
+        other => panic!("Expected a too many redirects error, got: {:?}", other),
+    }
+}
@@ -0,0 +1,166 @@
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+
+mod util;
+
+/// Answers every json-rpc request with its method name, and `eth_fail` with an error
+fn answer(request: &serde_json::Value) -> serde_json::Value {
+    match request["method"].as_str() {
+        Some("eth_fail") => serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "error": { "code": -32601, "message": "method not found" },
+        }),
+        method => serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": method,
+        }),
+    }
+}
+
+#[actix_rt::test]
+async fn test_http_requests_reuse_the_connection() {
+    let addr = util::spawn_http_server(|request| {
+        // Every request is sent on the first connection and accepts gzip responses
+        assert_eq!(request.connection, 0);
+        assert!(request.header("accept-encoding").unwrap().contains("gzip"));
+        assert_eq!(request.header("x-team"), Some("mempool"));
+        (200, answer(&request.body))
+    })
+    .await;
+
+    let mut config = HttpConnectionConfig::new(&format!("http://{}/v2/key", addr));
+    config.headers = vec![("x-team".to_string(), "mempool".to_string())];
+    let connection = config.connect().unwrap();
+
+    for _ in 0..3 {
+        let method: String = connection.request("eth_blockNumber", ()).await.unwrap();
+        assert_eq!(method, "eth_blockNumber");
+    }
+
+    match connection.request::<_, String>("eth_fail", ()).await {
+        Err(AlchemyConnectionError::RpcError(e)) => assert_eq!(e.code, -32601),
+        other => panic!("Expected a json-rpc error, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_http_gzip_responses() {
+    let addr = util::spawn_gzip_http_server(|request| (200, answer(&request.body))).await;
+
+    let connection = HttpConnectionConfig::new(&format!("http://{}", addr))
+        .connect()
+        .unwrap();
+    let method: String = connection.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(method, "eth_blockNumber");
+    match connection.request::<_, String>("eth_fail", ()).await {
+        Err(AlchemyConnectionError::RpcError(e)) => assert_eq!(e.code, -32601),
+        other => panic!("Expected a json-rpc error, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_http_batch() {
+    // The responses arrive in reverse order
+    let addr = util::spawn_http_server(|request| {
+        let requests = request.body.as_array().unwrap();
+        assert_eq!(requests.len(), 3);
+        let responses = requests.iter().rev().map(answer).collect::<Vec<_>>();
+        (200, serde_json::Value::Array(responses))
+    })
+    .await;
+
+    let connection = HttpConnectionConfig::new(&format!("http://{}", addr))
+        .connect()
+        .unwrap();
+    let mut batch = JsonRpcBatch::new();
+    batch.push("eth_blockNumber", ()).unwrap();
+    batch.push("eth_fail", ["0x1234"]).unwrap();
+    batch.push("eth_chainId", ()).unwrap();
+    assert_eq!(batch.calls[0].1, serde_json::json!([]));
+
+    let results = connection.batch(&batch).await.unwrap();
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0], Ok(serde_json::json!("eth_blockNumber")));
+    assert_eq!(results[1].as_ref().unwrap_err().code, -32601);
+    assert_eq!(results[2], Ok(serde_json::json!("eth_chainId")));
+
+    // Nothing is sent for an empty batch
+    assert!(connection
+        .batch(&JsonRpcBatch::new())
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[actix_rt::test]
+async fn test_http_rejections() {
+    let addr = util::spawn_http_server(|request| match request.body["method"].as_str() {
+        Some("eth_limited") => (429, serde_json::json!({})),
+        Some("eth_limitedRpc") => (
+            429,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request.body["id"],
+                "error": { "code": 429, "message": "too many requests" },
+            }),
+        ),
+        _ => (
+            400,
+            serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request.body["id"],
+                "error": { "code": -32600, "message": "invalid request" },
+            }),
+        ),
+    })
+    .await;
+
+    let connection = HttpConnectionConfig::new(&format!("http://{}", addr))
+        .connect()
+        .unwrap();
+    match connection.request::<_, String>("eth_limited", ()).await {
+        Err(AlchemyConnectionError::RateLimited(retry_after)) => {
+            assert_eq!(retry_after, Some(std::time::Duration::from_secs(2)))
+        }
+        other => panic!("Expected a rate limit error, got: {:?}", other),
+    }
+    match connection.request::<_, String>("eth_limitedRpc", ()).await {
+        Err(AlchemyConnectionError::RateLimited(retry_after)) => {
+            assert_eq!(retry_after, Some(std::time::Duration::from_secs(2)))
+        }
+        other => panic!("Expected a rate limit error, got: {:?}", other),
+    }
+    match connection.request::<_, String>("eth_invalid", ()).await {
+        Err(AlchemyConnectionError::RpcError(e)) => assert_eq!(e.code, -32600),
+        other => panic!("Expected a json-rpc error, got: {:?}", other),
+    }
+
+    match HttpConnectionConfig::new("ws://localhost").connect() {
+        Err(AlchemyConnectionError::UnsupportedScheme(scheme)) => assert_eq!(scheme, "ws"),
+        other => panic!("Expected an unsupported scheme error, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_manager_over_http() {
+    let addr = util::spawn_http_server(|request| (200, answer(&request.body))).await;
+
+    let mut manager = AlchemyManager::builder()
+        .with_connector_type(AlchemyConnectorType::Http)
+        .with_url(&format!("http://{}/v2/key", addr))
+        .build()
+        .unwrap();
+    manager.connect().await.unwrap();
+    let method: String = manager.request("eth_gasPrice", ()).await.unwrap();
+    assert_eq!(method, "eth_gasPrice");
+
+    // An api key resolves to the network's http endpoint
+    let manager = AlchemyManager::builder()
+        .with_connector_type(AlchemyConnectorType::Http)
+        .with_api_key("key")
+        .with_network(AlchemyNetwork::BaseMainnet)
+        .build()
+        .unwrap();
+    assert_eq!(manager.url, AlchemyNetwork::BaseMainnet.http_url("key"));
+}
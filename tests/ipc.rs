@@ -0,0 +1,404 @@
+#![cfg(unix)]
+
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+use futures::StreamExt;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::UnixListener,
+};
+
+mod util;
+
+/// Binds a unix socket at a fresh path in the temporary directory
+fn bind(name: &str) -> (String, UnixListener) {
+    let path = std::env::temp_dir().join(format!("alchemy-rs-{}-{}.ipc", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = UnixListener::bind(&path).unwrap();
+    (path.to_string_lossy().into_owned(), listener)
+}
+
+#[actix_rt::test]
+async fn test_ipc_subscription_and_request() {
+    let (path, listener) = bind("subscription");
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(request["method"], "eth_subscribe");
+        assert_eq!(request["params"][0], "newHeads");
+        let sub_id = util::subscription_hex(1);
+
+        // The confirmation and notification arrive undelimited, the notification split across writes
+        let confirmation = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": sub_id,
+        })
+        .to_string();
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "eth_subscription",
+            "params": {
+                "subscription": sub_id,
+                "result": Block::<TxHash>::default(),
+            },
+        })
+        .to_string();
+        let (head, tail) = notification.split_at(notification.len() / 2);
+        writer
+            .write_all(format!("{}{}", confirmation, head).as_bytes())
+            .await
+            .unwrap();
+        writer.flush().await.unwrap();
+        tokio::task::yield_now().await;
+        writer.write_all(tail.as_bytes()).await.unwrap();
+
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(request["method"], "eth_blockNumber");
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": "0x10",
+        });
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await
+            .unwrap();
+
+        while lines.next_line().await.unwrap().is_some() {}
+    });
+
+    let mut manager = AlchemyManager::new(&path, Some(AlchemyConnectorType::Ipc));
+    manager.connect().await.unwrap();
+
+    let mut heads = manager.subscribe_new_heads().await.unwrap();
+    let head = heads.next().await.unwrap().unwrap();
+    assert_eq!(head, Block::<TxHash>::default());
+
+    let block_number: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(block_number, U64::from(16));
+
+    drop(heads);
+    drop(manager);
+    server.await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_ipc_connection_closed_ends_subscriptions() {
+    let (path, listener) = bind("closed");
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": util::subscription_hex(1),
+        });
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await
+            .unwrap();
+
+        // Close once the subscription is routed, ie after answering the next request
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": "0x10",
+        });
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await
+            .unwrap();
+    });
+
+    let mut manager = AlchemyManager::new(&path, Some(AlchemyConnectorType::Ipc));
+    manager.connect().await.unwrap();
+    let mut hashes = manager.subscribe_new_pending_transactions().await.unwrap();
+    let _: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    server.await.unwrap();
+
+    // The connection isn't re-established, so the subscription ends
+    assert!(hashes.next().await.is_none());
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_ipc_connect_to_missing_socket() {
+    let mut manager = AlchemyManager::new(
+        "/nonexistent/alchemy-rs.ipc",
+        Some(AlchemyConnectorType::Ipc),
+    );
+    match manager.connect().await {
+        Err(AlchemyConnectionError::RawStreamError(_)) => (),
+        other => panic!("Expected a stream error, got: {:?}", other.map(|_| ())),
+    }
+}
+
+#[test]
+fn test_ipc_builder_requires_a_socket_path() {
+    let manager = AlchemyManager::builder()
+        .with_connector_type(AlchemyConnectorType::Ipc)
+        .with_url("/tmp/reth.ipc")
+        .build()
+        .unwrap();
+    assert_eq!(manager.url, "/tmp/reth.ipc");
+
+    match AlchemyManager::builder()
+        .with_connector_type(AlchemyConnectorType::Ipc)
+        .with_api_key("key")
+        .build()
+    {
+        Err(AlchemyConnectionError::InvalidConfig(_)) => (),
+        other => panic!("Expected an invalid config error, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_ipc_values_split_across_many_reads() {
+    let (path, listener) = bind("framing");
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let sub_id = util::subscription_hex(1);
+        let confirmation = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": sub_id,
+        });
+        writer
+            .write_all(format!("{}\n", confirmation).as_bytes())
+            .await
+            .unwrap();
+
+        // Close once the subscription is routed, ie after answering the next request
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": "0x10",
+        });
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await
+            .unwrap();
+
+        // A notification far larger than a read, with brackets and escaped quotes in its strings,
+        // trickled in small writes, directly followed by a second one
+        let mut transaction = serde_json::to_value(Transaction::default()).unwrap();
+        transaction["input"] = serde_json::Value::String(format!("0x{}", "ab".repeat(64 * 1024)));
+        transaction["note"] = serde_json::Value::String("}]\\\"{[".into());
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "eth_subscription",
+            "params": {
+                "subscription": sub_id,
+                "result": transaction,
+            },
+        })
+        .to_string();
+        let stream = format!("{} {}", notification, notification);
+        for chunk in stream.as_bytes().chunks(1000) {
+            writer.write_all(chunk).await.unwrap();
+            writer.flush().await.unwrap();
+        }
+
+        while lines.next_line().await.unwrap().is_some() {}
+    });
+
+    let mut manager = AlchemyManager::new(&path, Some(AlchemyConnectorType::Ipc));
+    manager.connect().await.unwrap();
+    let mut transactions = manager
+        .subscribe_stream::<serde_json::Value>(vec!["newPendingTransactions".into()])
+        .await
+        .unwrap();
+    let _: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+
+    for _ in 0..2 {
+        let transaction = transactions.next().await.unwrap().unwrap();
+        assert_eq!(transaction["note"], "}]\\\"{[");
+        assert_eq!(transaction["input"].as_str().unwrap().len(), 2 + 128 * 1024);
+    }
+
+    drop(transactions);
+    drop(manager);
+    server.await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_ipc_short_subscription_ids() {
+    let (path, listener) = bind("short-ids");
+
+    // Geth trims the leading zeros of its subscription ids
+    let short_id = "0x9cef478923ff08bf67fde6c64013158";
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let confirmation = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": short_id,
+        });
+        let notification = serde_json::json!({
+            "jsonrpc": "2.0",
+            "method": "eth_subscription",
+            "params": {
+                "subscription": short_id,
+                "result": Block::<TxHash>::default(),
+            },
+        });
+        writer
+            .write_all(format!("{}\n{}\n", confirmation, notification).as_bytes())
+            .await
+            .unwrap();
+
+        // The subscription is cancelled with the id exactly as it was issued
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(request["method"], "eth_unsubscribe");
+        assert_eq!(request["params"][0], short_id);
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": "0x10",
+        });
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await
+            .unwrap();
+
+        while lines.next_line().await.unwrap().is_some() {}
+    });
+
+    let mut manager = AlchemyManager::new(&path, Some(AlchemyConnectorType::Ipc));
+    manager.connect().await.unwrap();
+    let mut heads = manager.subscribe_new_heads().await.unwrap();
+    assert_eq!(
+        heads.id(),
+        uuid::Uuid::parse_str("09cef478923ff08bf67fde6c64013158").unwrap()
+    );
+    assert_eq!(
+        heads.next().await.unwrap().unwrap(),
+        Block::<TxHash>::default()
+    );
+
+    // The unsubscribe is queued ahead of the request
+    drop(heads);
+    let _: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    drop(manager);
+    server.await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_ipc_reconnect_ends_previous_subscriptions() {
+    let (path, listener) = bind("reconnect");
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": util::subscription_hex(1),
+        });
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await
+            .unwrap();
+        while let Ok(Some(_)) = lines.next_line().await {}
+
+        // The second connection stays quiet
+        let (_second, _) = listener.accept().await.unwrap();
+        futures::future::pending::<()>().await;
+    });
+
+    let mut manager = AlchemyManager::new(&path, Some(AlchemyConnectorType::Ipc));
+    manager.connect().await.unwrap();
+    let mut heads = manager.subscribe_new_heads().await.unwrap();
+
+    // The previous connection is closed, ending its subscriptions rather than leaving them dangling
+    manager.connect().await.unwrap();
+    let next = tokio::time::timeout(std::time::Duration::from_secs(5), heads.next()).await;
+    assert!(next.unwrap().is_none());
+
+    server.abort();
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[actix_rt::test]
+async fn test_ipc_skips_stray_bytes() {
+    let (path, listener) = bind("stray");
+
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let (reader, mut writer) = tokio::io::split(stream);
+        let mut lines = BufReader::new(reader).lines();
+
+        // Garbage, including bytes that aren't utf-8, precedes and follows the response
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": "0x10",
+        });
+        let mut bytes = b"garbage\xff} ".to_vec();
+        bytes.extend_from_slice(response.to_string().as_bytes());
+        bytes.extend_from_slice(b"\n\x00oops\n");
+        writer.write_all(&bytes).await.unwrap();
+
+        // The connection keeps serving requests
+        let request: serde_json::Value =
+            serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+        let response = serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": "0x11",
+        });
+        writer
+            .write_all(format!("{}\n", response).as_bytes())
+            .await
+            .unwrap();
+
+        while lines.next_line().await.unwrap().is_some() {}
+    });
+
+    let mut manager = AlchemyManager::new(&path, Some(AlchemyConnectorType::Ipc));
+    manager.connect().await.unwrap();
+    let first: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(first, U64::from(16));
+    let second: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(second, U64::from(17));
+
+    drop(manager);
+    server.await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,107 @@
+use std::time::Duration;
+
+use alchemy_rs::connectors::prelude::*;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+mod util;
+
+#[actix_rt::test]
+async fn test_keepalive_missed_pongs() {
+    // The server never reads from the socket, so pings are never answered
+    let addr = util::spawn_ws_server(|_, socket| async move {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        drop(socket);
+    })
+    .await;
+
+    let mut connection = RawAlchemyConnection::new().with_keepalive(Some(KeepaliveConfig {
+        ping_interval: Duration::from_millis(20),
+        max_missed_pongs: 2,
+        idle_timeout: None,
+        ..KeepaliveConfig::default()
+    }));
+    connection.connect(&format!("ws://{}", addr)).await.unwrap();
+    match connection.receive_text().await {
+        Err(AlchemyConnectionError::KeepaliveTimeout(2)) => (),
+        other => panic!("Expected a keepalive timeout, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_keepalive_idle_timeout() {
+    let addr = util::spawn_ws_server(|_, socket| async move {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        drop(socket);
+    })
+    .await;
+
+    let mut connection = RawAlchemyConnection::new().with_keepalive(Some(KeepaliveConfig {
+        ping_interval: Duration::from_secs(1),
+        max_missed_pongs: 2,
+        idle_timeout: Some(Duration::from_millis(50)),
+        ..KeepaliveConfig::default()
+    }));
+    connection.connect(&format!("ws://{}", addr)).await.unwrap();
+    match connection.receive_text().await {
+        Err(AlchemyConnectionError::IdleTimeout(_)) => (),
+        other => panic!("Expected an idle timeout, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_keepalive_pongs_keep_connection_alive() {
+    // The server answers pings while reading, and only sends a message after several ping intervals
+    let addr = util::spawn_ws_server(|_, socket| async move {
+        let util::MockSocket {
+            mut sender,
+            mut receiver,
+        } = socket;
+        tokio::spawn(async move {
+            let mut data = vec![];
+            while receiver.receive_data(&mut data).await.is_ok() {}
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        sender.send_text("hello").await.unwrap();
+        sender.flush().await.unwrap();
+        tokio::time::sleep(Duration::from_secs(5)).await;
+    })
+    .await;
+
+    let mut connection = RawAlchemyConnection::new().with_keepalive(Some(KeepaliveConfig {
+        ping_interval: Duration::from_millis(20),
+        max_missed_pongs: 2,
+        idle_timeout: Some(Duration::from_millis(100)),
+        ..KeepaliveConfig::default()
+    }));
+    connection.connect(&format!("ws://{}", addr)).await.unwrap();
+    assert_eq!(connection.receive_text().await.unwrap(), "hello");
+    assert!(connection.last_rtt().is_some());
+}
+
+#[actix_rt::test]
+async fn test_keepalive_write_timeout() {
+    // The server stops reading after the handshake, so the pings eventually fill the small pipe
+    let (transport, mut listener) = MemoryTransport::new(512);
+    tokio::spawn(async move {
+        let stream = listener.accept().await.unwrap();
+        let socket = util::MockSocket::accept(stream.compat()).await;
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        drop(socket);
+    });
+
+    let mut connection = RawAlchemyConnection::new()
+        .with_transport(std::sync::Arc::new(transport))
+        .with_keepalive(Some(KeepaliveConfig {
+            ping_interval: Duration::from_millis(5),
+            max_missed_pongs: 1000,
+            idle_timeout: None,
+            write_timeout: Duration::from_millis(50),
+        }));
+    connection.connect("ws://memory").await.unwrap();
+    match connection.receive_text().await {
+        Err(AlchemyConnectionError::WriteTimeout(timeout)) => {
+            assert_eq!(timeout, Duration::from_millis(50))
+        }
+        other => panic!("Expected a write timeout, got: {:?}", other),
+    }
+}
@@ -88,3 +88,18 @@ fn test_inbound_alchemy_subscription_message_result_serialization() {
     // Make sure the deserialized message is the same as the constructed message
     assert_eq!(constructed, deserialized);
 }
+
+#[test]
+fn test_inbound_short_subscription_id_deserialization() {
+    // Nodes like geth trim the leading zeros of their subscription ids
+    let trimmed = r#"{
+        "id": 1,
+        "result": "0x9cef478923ff08bf67fde6c64013158",
+        "jsonrpc": "2.0"
+    }"#;
+    let deserialized = serde_json::from_str::<AlchemySubscriptionMessageResult>(trimmed).unwrap();
+    assert_eq!(
+        deserialized.result,
+        uuid::Uuid::parse_str("09cef478923ff08bf67fde6c64013158").unwrap()
+    );
+}
@@ -0,0 +1,110 @@
+use std::{convert::TryFrom, str::FromStr};
+
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+
+#[test]
+fn test_network_endpoints() {
+    let network = AlchemyNetwork::ArbitrumMainnet;
+    assert_eq!(network.host(), "arb-mainnet.g.alchemy.com");
+    assert_eq!(
+        network.ws_url("key"),
+        "wss://arb-mainnet.g.alchemy.com/v2/key"
+    );
+    assert_eq!(
+        network.http_url("key"),
+        "https://arb-mainnet.g.alchemy.com/v2/key"
+    );
+    assert_eq!(AlchemyNetwork::from_str("arb-mainnet").unwrap(), network);
+    assert!(AlchemyNetwork::from_str("arbitrum").is_err());
+}
+
+#[test]
+fn test_network_chain_conversions() {
+    assert_eq!(
+        AlchemyNetwork::try_from(Chain::Mainnet).unwrap(),
+        AlchemyNetwork::EthMainnet
+    );
+    assert_eq!(
+        Chain::try_from(AlchemyNetwork::OptimismMainnet).unwrap(),
+        Chain::Optimism
+    );
+
+    // Every network ethers-rs knows of round-trips
+    for network in AlchemyNetwork::ALL {
+        assert_eq!(
+            AlchemyNetwork::from_chain_id(network.chain_id()),
+            Some(network)
+        );
+        if let Ok(chain) = Chain::try_from(network) {
+            assert_eq!(AlchemyNetwork::try_from(chain).unwrap(), network);
+        }
+    }
+
+    // Networks without an ethers-rs chain, and chains alchemy doesn't serve, fail
+    assert!(Chain::try_from(AlchemyNetwork::BaseMainnet).is_err());
+    assert!(AlchemyNetwork::try_from(Chain::Fantom).is_err());
+}
+
+#[test]
+fn test_network_capabilities() {
+    assert!(AlchemyNetwork::EthMainnet.supports(Capability::PendingTransactions));
+    assert!(!AlchemyNetwork::ArbitrumMainnet.supports(Capability::PendingTransactions));
+    assert!(!AlchemyNetwork::ZksyncMainnet.supports(Capability::MinedTransactions));
+    assert_eq!(
+        Capability::for_method("trace_block"),
+        Some(Capability::TraceApi)
+    );
+    assert_eq!(
+        Capability::for_method("debug_traceTransaction"),
+        Some(Capability::DebugApi)
+    );
+    assert_eq!(Capability::for_method("getNFTs"), Some(Capability::NftApi));
+    assert_eq!(Capability::for_method("eth_blockNumber"), None);
+    assert!(AlchemyNetwork::ArbitrumMainnet.supports(Capability::DebugApi));
+    assert!(!AlchemyNetwork::PolygonMumbai.supports(Capability::DebugApi));
+    assert!(AlchemyNetwork::BaseMainnet.supports(Capability::NftApi));
+    assert!(!AlchemyNetwork::OptimismSepolia.supports(Capability::NftApi));
+}
+
+#[actix_rt::test]
+async fn test_unsupported_capabilities_fail_early() {
+    // Nothing is sent, so the manager doesn't even need to connect
+    let mut manager = AlchemyManager::from_network(AlchemyNetwork::ArbitrumMainnet, "key", None);
+    match manager.subscribe(None, None).await {
+        Err(AlchemyConnectionError::UnsupportedOnNetwork(
+            Capability::PendingTransactions,
+            AlchemyNetwork::ArbitrumMainnet,
+        )) => (),
+        other => panic!("Expected an unsupported capability, got: {:?}", other),
+    }
+
+    let mut manager = AlchemyManager::from_network(AlchemyNetwork::ZksyncMainnet, "key", None);
+    match manager
+        .request::<_, serde_json::Value>("trace_block", ["latest"])
+        .await
+    {
+        Err(AlchemyConnectionError::UnsupportedOnNetwork(Capability::TraceApi, _)) => (),
+        other => panic!("Expected an unsupported capability, got: {:?}", other),
+    }
+    match manager
+        .request::<_, serde_json::Value>("debug_traceTransaction", ["0x00"])
+        .await
+    {
+        Err(AlchemyConnectionError::UnsupportedOnNetwork(Capability::DebugApi, _)) => (),
+        other => panic!("Expected an unsupported capability, got: {:?}", other),
+    }
+    match manager
+        .request::<_, serde_json::Value>("getNFTs", [serde_json::json!({ "owner": "0x00" })])
+        .await
+    {
+        Err(AlchemyConnectionError::UnsupportedOnNetwork(Capability::NftApi, _)) => (),
+        other => panic!("Expected an unsupported capability, got: {:?}", other),
+    }
+    match manager
+        .subscribe_stream::<serde_json::Value>(vec!["alchemy_minedTransactions".into()])
+        .await
+    {
+        Err(AlchemyConnectionError::UnsupportedOnNetwork(Capability::MinedTransactions, _)) => (),
+        other => panic!("Expected an unsupported capability, got: {:?}", other),
+    }
+}
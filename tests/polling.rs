@@ -0,0 +1,253 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+use futures::StreamExt;
+
+mod util;
+
+/// A json-rpc response to the request
+fn respond(request: &serde_json::Value, result: serde_json::Value) -> (u16, serde_json::Value) {
+    (
+        200,
+        serde_json::json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+    )
+}
+
+/// Builds a manager polling the stand-in server every few milliseconds
+fn manager(addr: std::net::SocketAddr) -> AlchemyManager {
+    AlchemyManager::builder()
+        .with_connector_type(AlchemyConnectorType::Http)
+        .with_url(&format!("http://{}", addr))
+        .with_poll_interval(Duration::from_millis(10))
+        .build()
+        .unwrap()
+}
+
+#[actix_rt::test]
+async fn test_polled_new_heads_survive_filter_expiry() {
+    let methods = Arc::new(Mutex::new(Vec::<String>::new()));
+    let recorded = Arc::clone(&methods);
+    let addr = util::spawn_http_server(move |request| {
+        let body = request.body;
+        let method = body["method"].as_str().unwrap().to_string();
+        let mut methods = recorded.lock().unwrap();
+        methods.push(method.clone());
+        let installed = methods
+            .iter()
+            .filter(|m| *m == "eth_newBlockFilter")
+            .count();
+        let polls = methods
+            .iter()
+            .filter(|m| *m == "eth_getFilterChanges")
+            .count();
+        match method.as_str() {
+            "eth_newBlockFilter" => respond(&body, serde_json::json!(format!("0x{}", installed))),
+            // The first filter expires after one poll
+            "eth_getFilterChanges" if body["params"][0] == "0x1" && polls > 1 => (
+                200,
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": body["id"],
+                    "error": { "code": -32000, "message": "filter not found" },
+                }),
+            ),
+            "eth_getFilterChanges" if polls == 1 => {
+                respond(&body, serde_json::json!([H256::from_low_u64_be(1)]))
+            }
+            "eth_getFilterChanges" if body["params"][0] == "0x2" && installed == 2 => {
+                let changes = if methods
+                    .iter()
+                    .filter(|m| *m == "eth_getBlockByHash")
+                    .count()
+                    == 1
+                {
+                    serde_json::json!([H256::from_low_u64_be(2)])
+                } else {
+                    serde_json::json!([])
+                };
+                respond(&body, changes)
+            }
+            "eth_getFilterChanges" => respond(&body, serde_json::json!([])),
+            "eth_getBlockByHash" => {
+                let hash: H256 = serde_json::from_value(body["params"][0].clone()).unwrap();
+                assert_eq!(body["params"][1], false);
+                let block = Block::<TxHash> {
+                    hash: Some(hash),
+                    ..Default::default()
+                };
+                respond(&body, serde_json::to_value(block).unwrap())
+            }
+            "eth_uninstallFilter" => respond(&body, serde_json::json!(true)),
+            other => panic!("Unexpected method: {}", other),
+        }
+    })
+    .await;
+
+    let mut manager = manager(addr);
+    manager.connect().await.unwrap();
+    let mut heads = manager.subscribe_new_heads().await.unwrap();
+    let first = heads.next().await.unwrap().unwrap();
+    assert_eq!(first.hash, Some(H256::from_low_u64_be(1)));
+    let second = heads.next().await.unwrap().unwrap();
+    assert_eq!(second.hash, Some(H256::from_low_u64_be(2)));
+
+    // Dropping the stream uninstalls the current filter
+    drop(heads);
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !methods
+            .lock()
+            .unwrap()
+            .contains(&"eth_uninstallFilter".to_string())
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+}
+
+#[actix_rt::test]
+async fn test_polled_pending_transactions_are_filtered_by_address() {
+    let target = Address::from_low_u64_be(0xbeef);
+    let polls = Arc::new(Mutex::new(0));
+    let addr = util::spawn_http_server(move |request| {
+        let body = request.body;
+        match body["method"].as_str().unwrap() {
+            "eth_newPendingTransactionFilter" => respond(&body, serde_json::json!("0xa")),
+            "eth_getFilterChanges" => {
+                let mut polls = polls.lock().unwrap();
+                *polls += 1;
+                match *polls {
+                    1 => respond(
+                        &body,
+                        serde_json::json!([H256::from_low_u64_be(1), H256::from_low_u64_be(2)]),
+                    ),
+                    _ => respond(&body, serde_json::json!([])),
+                }
+            }
+            "eth_getTransactionByHash" => {
+                let hash: H256 = serde_json::from_value(body["params"][0].clone()).unwrap();
+                let to = if hash == H256::from_low_u64_be(1) {
+                    Address::from_low_u64_be(0xdead)
+                } else {
+                    Address::from_low_u64_be(0xbeef)
+                };
+                let transaction = Transaction {
+                    hash,
+                    to: Some(to),
+                    ..Default::default()
+                };
+                respond(&body, serde_json::to_value(transaction).unwrap())
+            }
+            "eth_uninstallFilter" => respond(&body, serde_json::json!(true)),
+            other => panic!("Unexpected method: {}", other),
+        }
+    })
+    .await;
+
+    let mut manager = manager(addr);
+    manager.connect().await.unwrap();
+    let mut transactions = manager
+        .subscribe_pending_transactions(Some(target), None)
+        .await
+        .unwrap();
+    let transaction = transactions.next().await.unwrap().unwrap();
+    assert_eq!(transaction.hash, H256::from_low_u64_be(2));
+    assert_eq!(transaction.to, Some(target));
+}
+
+#[actix_rt::test]
+async fn test_polling_errors_are_retried_and_unsubscribe_uninstalls_the_filter() {
+    let methods = Arc::new(Mutex::new(Vec::<String>::new()));
+    let recorded = Arc::clone(&methods);
+    let addr = util::spawn_http_server(move |request| {
+        let body = request.body;
+        let method = body["method"].as_str().unwrap().to_string();
+        let mut methods = recorded.lock().unwrap();
+        methods.push(method.clone());
+        match method.as_str() {
+            "eth_newPendingTransactionFilter" => respond(&body, serde_json::json!("0xa")),
+            // The first poll fails with an error unrelated to the filter
+            "eth_getFilterChanges" if methods.iter().filter(|m| *m == &method).count() == 1 => (
+                200,
+                serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": body["id"],
+                    "error": { "code": -32603, "message": "internal error" },
+                }),
+            ),
+            "eth_getFilterChanges" => respond(&body, serde_json::json!([H256::from_low_u64_be(1)])),
+            "eth_getTransactionByHash" => {
+                let transaction = Transaction {
+                    hash: H256::from_low_u64_be(1),
+                    ..Default::default()
+                };
+                respond(&body, serde_json::to_value(transaction).unwrap())
+            }
+            "eth_uninstallFilter" => {
+                assert_eq!(body["params"][0], "0xa");
+                respond(&body, serde_json::json!(true))
+            }
+            other => panic!("Unexpected method: {}", other),
+        }
+    })
+    .await;
+
+    let mut manager = manager(addr);
+    manager.connect().await.unwrap();
+    let sub_id = manager.subscribe(None, None).await.unwrap();
+    manager.receive(sub_id).await.unwrap();
+
+    // The failed poll is retried with the same filter
+    assert!(manager.unsubscribe(sub_id).await.unwrap());
+    let methods = methods.lock().unwrap();
+    let installs = methods
+        .iter()
+        .filter(|m| *m == "eth_newPendingTransactionFilter")
+        .count();
+    assert_eq!(installs, 1);
+    assert_eq!(methods.last().unwrap(), "eth_uninstallFilter");
+}
+
+#[actix_rt::test]
+async fn test_unsupported_polled_subscription() {
+    let addr =
+        util::spawn_http_server(|request| respond(&request.body, serde_json::json!(null))).await;
+
+    let mut manager = manager(addr);
+    manager.connect().await.unwrap();
+    match manager
+        .subscribe_stream::<serde_json::Value>(vec![serde_json::json!("syncing")])
+        .await
+    {
+        Err(AlchemyConnectionError::UnsupportedSubscription(_)) => (),
+        other => panic!(
+            "Expected an unsupported subscription error, got: {:?}",
+            other.map(|_| ())
+        ),
+    }
+}
+
+#[test]
+fn test_pending_transaction_filter_options() {
+    let options = serde_json::json!({
+        "toAddress": ["0x000000000000000000000000000000000000beef"],
+        "fromAddress": "0x000000000000000000000000000000000000dead",
+        "hashesOnly": true,
+    });
+    let filter = PendingTransactionFilter::from_options(Some(&options)).unwrap();
+    assert_eq!(filter.to, vec![Address::from_low_u64_be(0xbeef)]);
+    assert_eq!(filter.from, vec![Address::from_low_u64_be(0xdead)]);
+    assert!(filter.hashes_only);
+
+    let transaction = Transaction {
+        from: Address::from_low_u64_be(0xdead),
+        ..Default::default()
+    };
+    assert!(filter.matches(&transaction));
+    assert!(!filter.matches(&Transaction::default()));
+    assert!(PendingTransactionFilter::default().matches(&Transaction::default()));
+}
@@ -0,0 +1,138 @@
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+use futures::StreamExt;
+
+mod util;
+
+/// Spawns a server confirming every subscription with a notification, numbering subscriptions per connection
+///
+/// Both connections hand out the same subscription ids, which each connection routes on its own.
+/// Requests are answered with the index of the connection.
+async fn spawn_server() -> std::net::SocketAddr {
+    util::spawn_ws_server(|index, mut socket| async move {
+        let mut subscriptions = 0;
+        while let Some(request) = socket.recv_json().await {
+            match request["method"].as_str() {
+                Some("eth_subscribe") => {
+                    subscriptions += 1;
+                    let sub_id = util::subscription_hex(subscriptions);
+                    socket
+                        .respond(&request, serde_json::Value::String(sub_id.clone()))
+                        .await;
+                    socket
+                        .send_json(util::pending_transaction_notification(&sub_id))
+                        .await;
+                }
+                Some("eth_unsubscribe") => socket.respond(&request, true.into()).await,
+                _ => {
+                    socket
+                        .respond(&request, format!("0x{:x}", index).into())
+                        .await
+                }
+            }
+        }
+    })
+    .await
+}
+
+/// The number of subscriptions of every pooled connection
+fn subscription_counts(manager: &AlchemyManager) -> Vec<usize> {
+    match &manager.connector {
+        AlchemyConnector::Pool(Some(pool)) => pool.subscription_counts(),
+        other => panic!("Expected a connected pool, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_pool_places_subscriptions_on_the_least_loaded_connection() {
+    let addr = spawn_server().await;
+    let mut manager = AlchemyManager::builder()
+        .with_connector_type(AlchemyConnectorType::Pool)
+        .with_url(&format!("ws://{}", addr))
+        .with_pool_size(2)
+        .with_max_subscriptions_per_connection(2)
+        .build()
+        .unwrap();
+    manager.connect().await.unwrap();
+    assert_eq!(subscription_counts(&manager), vec![0, 0]);
+
+    // Subscriptions alternate between the connections, and each gets its own notification
+    let mut streams = vec![];
+    for _ in 0..4 {
+        let mut stream = manager
+            .subscribe_pending_transactions(None, None)
+            .await
+            .unwrap();
+        stream.next().await.unwrap().unwrap();
+        streams.push(stream);
+    }
+    assert_eq!(subscription_counts(&manager), vec![2, 2]);
+
+    match manager.subscribe_pending_transactions(None, None).await {
+        Err(AlchemyConnectionError::PoolExhausted(2)) => (),
+        other => panic!(
+            "Expected an exhausted pool, got: {:?}",
+            other.map(|s| s.id())
+        ),
+    }
+
+    // Dropping a stream makes room on its connection, which also takes the requests now
+    drop(streams.remove(0));
+    let mut counts = subscription_counts(&manager);
+    counts.sort_unstable();
+    assert_eq!(counts, vec![1, 2]);
+    let emptier = subscription_counts(&manager)
+        .iter()
+        .position(|count| *count == 1)
+        .unwrap();
+    let index: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(index.as_usize(), emptier);
+
+    let mut stream = manager
+        .subscribe_pending_transactions(None, None)
+        .await
+        .unwrap();
+    stream.next().await.unwrap().unwrap();
+    assert_eq!(subscription_counts(&manager), vec![2, 2]);
+}
+
+#[actix_rt::test]
+async fn test_pool_subscriptions_are_received_and_unsubscribed() {
+    let addr = spawn_server().await;
+    let mut manager =
+        AlchemyManager::new(&format!("ws://{}", addr), Some(AlchemyConnectorType::Pool))
+            .with_pool_size(3);
+    manager.connect().await.unwrap();
+
+    let mut sub_ids = vec![];
+    for _ in 0..3 {
+        let sub_id = manager.subscribe(None, None).await.unwrap();
+        let message = manager.receive(sub_id).await.unwrap();
+        assert_eq!(message.subscription(), sub_id);
+        sub_ids.push(sub_id);
+    }
+    assert_eq!(subscription_counts(&manager), vec![1, 1, 1]);
+
+    assert!(manager.unsubscribe(sub_ids[1]).await.unwrap());
+    assert_eq!(subscription_counts(&manager).iter().sum::<usize>(), 2);
+    match manager.unsubscribe(sub_ids[1]).await {
+        Err(AlchemyConnectionError::UnknownSubscription(id)) => assert_eq!(id, sub_ids[1]),
+        other => panic!("Expected an unknown subscription, got: {:?}", other),
+    }
+}
+
+#[test]
+fn test_pool_configuration_is_validated() {
+    for builder in [
+        AlchemyManager::builder().with_pool_size(0),
+        AlchemyManager::builder().with_max_subscriptions_per_connection(0),
+    ] {
+        match builder
+            .with_connector_type(AlchemyConnectorType::Pool)
+            .with_url("ws://localhost")
+            .build()
+        {
+            Err(AlchemyConnectionError::InvalidConfig(_)) => (),
+            other => panic!("Expected an invalid config, got: {:?}", other),
+        }
+    }
+}
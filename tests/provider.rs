@@ -0,0 +1,66 @@
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+use futures::StreamExt;
+
+mod util;
+
+#[actix_rt::test]
+async fn test_provider_subscriptions() {
+    // Confirm two subscriptions, notify both, then expect the first to be unsubscribed
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        for n in 1..=2 {
+            let request = socket.recv_json().await.unwrap();
+            assert_eq!(request["method"], "eth_subscribe");
+            assert_eq!(request["params"][0], "alchemy_pendingTransactions");
+            socket
+                .respond(
+                    &request,
+                    serde_json::Value::String(util::subscription_hex(n)),
+                )
+                .await;
+        }
+        // ethers registers a subscription only once confirmed, so notify after the next request
+        let request = socket.recv_json().await.unwrap();
+        assert_eq!(request["method"], "eth_blockNumber");
+        socket
+            .respond(&request, serde_json::Value::String("0x1".into()))
+            .await;
+        for n in [1, 2] {
+            socket
+                .send_json(util::pending_transaction_notification(
+                    &util::subscription_hex(n),
+                ))
+                .await;
+        }
+
+        let request = socket.recv_json().await.unwrap();
+        assert_eq!(request["method"], "eth_unsubscribe");
+        socket
+            .respond(&request, serde_json::Value::Bool(true))
+            .await;
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(
+        &format!("ws://{}", addr),
+        Some(AlchemyConnectorType::Provider),
+    );
+    manager.connect().await.unwrap();
+
+    // Both subscription apis deliver the same types as the raw connector
+    let sub_id = manager.subscribe(None, None).await.unwrap();
+    let mut stream = manager
+        .subscribe_pending_transactions(None, None)
+        .await
+        .unwrap();
+    assert_eq!(sub_id, uuid::Uuid::from_u128(1));
+    assert_eq!(stream.id(), uuid::Uuid::from_u128(2));
+    let _: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+
+    let AlchemyInnerResponse::PendingTransactionResult(result) =
+        manager.receive(sub_id).await.unwrap().result;
+    assert_eq!(result.subscription, sub_id);
+    assert!(stream.next().await.unwrap().is_ok());
+
+    assert!(manager.unsubscribe(sub_id).await.unwrap());
+}
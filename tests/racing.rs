@@ -0,0 +1,148 @@
+use std::time::{Duration, Instant};
+
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+use futures::StreamExt;
+
+mod util;
+
+/// A pending transaction notification carrying a transaction with the given hash
+fn transaction_notification(subscription: &str, hash: u64) -> serde_json::Value {
+    let transaction = Transaction {
+        hash: H256::from_low_u64_be(hash),
+        ..Default::default()
+    };
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "eth_subscription",
+        "params": { "subscription": subscription, "result": transaction },
+    })
+}
+
+#[actix_rt::test]
+async fn test_racing_delivers_each_transaction_once() {
+    // Each connection sees one transaction well before the other connection does
+    let addr = util::spawn_ws_server(|index, mut socket| async move {
+        let (first, second) = if index == 0 { (1, 2) } else { (2, 1) };
+        while let Some(request) = socket.recv_json().await {
+            if request["method"] != "eth_subscribe" {
+                socket.respond(&request, true.into()).await;
+                continue;
+            }
+            let sub_id = util::subscription_hex(1);
+            socket
+                .respond(&request, serde_json::Value::String(sub_id.clone()))
+                .await;
+            socket
+                .send_json(transaction_notification(&sub_id, first))
+                .await;
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            socket
+                .send_json(transaction_notification(&sub_id, second))
+                .await;
+        }
+    })
+    .await;
+
+    let mut manager = AlchemyManager::builder()
+        .with_connector_type(AlchemyConnectorType::Pool)
+        .with_url(&format!("ws://{}", addr))
+        .with_pool_size(2)
+        .build()
+        .unwrap();
+    manager.connect().await.unwrap();
+
+    let mut race = manager.race_pending_transactions(None, None).await.unwrap();
+    assert_eq!(race.feeds(), 2);
+
+    let mut winners = vec![];
+    for _ in 0..2 {
+        let raced = race.next().await.unwrap().unwrap();
+        winners.push((raced.value.hash.to_low_u64_be(), raced.feed));
+    }
+    winners.sort_unstable();
+    assert_ne!(winners[0].1, winners[1].1);
+    assert_eq!(
+        winners.iter().map(|(hash, _)| *hash).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+
+    // The late duplicates are swallowed, and recorded as losses
+    assert!(
+        tokio::time::timeout(Duration::from_millis(500), race.next())
+            .await
+            .is_err()
+    );
+    for stats in race.stats() {
+        assert_eq!((stats.wins, stats.losses), (1, 1));
+        assert!(stats.mean_lag().unwrap() >= Duration::from_millis(100));
+    }
+}
+
+#[test]
+fn test_seen_cache_forgets_the_least_recently_seen_key() {
+    let mut seen = SeenCache::new(2);
+    let start = Instant::now();
+    assert_eq!(seen.see(1, start), None);
+    assert_eq!(seen.see(2, start + Duration::from_secs(1)), None);
+
+    // Seeing 1 again refreshes it, so 2 is forgotten first
+    assert_eq!(seen.see(1, start + Duration::from_secs(2)), Some(start));
+    assert_eq!(seen.see(3, start), None);
+    assert_eq!(seen.len(), 2);
+    assert_eq!(seen.see(2, start), None);
+    assert_eq!(seen.see(1, start), None);
+
+    // Repeated sightings don't grow the cache
+    for _ in 0..100 {
+        seen.see(1, start);
+    }
+    assert_eq!(seen.len(), 2);
+}
+
+#[actix_rt::test]
+async fn test_racing_places_feeds_on_the_pool() {
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        let mut subscriptions = 0;
+        while let Some(request) = socket.recv_json().await {
+            if request["method"] != "eth_subscribe" {
+                socket.respond(&request, true.into()).await;
+                continue;
+            }
+            subscriptions += 1;
+            let sub_id = util::subscription_hex(subscriptions);
+            socket
+                .respond(&request, serde_json::Value::String(sub_id))
+                .await;
+        }
+    })
+    .await;
+
+    let mut manager = AlchemyManager::builder()
+        .with_connector_type(AlchemyConnectorType::Pool)
+        .with_url(&format!("ws://{}", addr))
+        .with_pool_size(2)
+        .with_max_subscriptions_per_connection(1)
+        .build()
+        .unwrap();
+    manager.connect().await.unwrap();
+
+    // The first connection is full, so the race only gets a feed on the second one
+    manager.subscribe(None, None).await.unwrap();
+    let race = manager.race_pending_transactions(None, None).await.unwrap();
+    assert_eq!(race.feeds(), 1);
+    assert_eq!(race.indexes(), [1]);
+    assert_eq!(race.stats().len(), 2);
+    match &manager.connector {
+        AlchemyConnector::Pool(Some(pool)) => assert_eq!(pool.subscription_counts(), [1, 1]),
+        other => panic!("Expected a connected pool, got: {:?}", other),
+    }
+
+    // Without room left, racing fails like a single subscription
+    match manager.race_pending_transactions(None, None).await {
+        Err(AlchemyConnectionError::PoolExhausted(1)) => (),
+        other => panic!(
+            "Expected an exhausted pool, got: {:?}",
+            other.map(|r| r.feeds())
+        ),
+    }
+}
@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use alchemy_rs::connectors::prelude::*;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+mod util;
+
+/// The self-signed certificate and key of the local tls server, issued for `localhost`
+const CERTIFICATE: &[u8] = include_bytes!("fixtures/localhost.crt");
+const PRIVATE_KEY: &[u8] = include_bytes!("fixtures/localhost.key");
+
+/// A tls connector trusting only the self-signed certificate
+fn tls_connector() -> async_tls::TlsConnector {
+    let mut config = rustls::ClientConfig::new();
+    let certificates = rustls::internal::pemfile::certs(&mut &*CERTIFICATE).unwrap();
+    config.root_store.add(&certificates[0]).unwrap();
+    async_tls::TlsConnector::from(Arc::new(config))
+}
+
+/// A tls acceptor serving the self-signed certificate
+fn tls_acceptor() -> async_tls::TlsAcceptor {
+    let certificates = rustls::internal::pemfile::certs(&mut &*CERTIFICATE).unwrap();
+    let mut keys = rustls::internal::pemfile::pkcs8_private_keys(&mut &*PRIVATE_KEY).unwrap();
+    let mut config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+    config
+        .set_single_cert(certificates, keys.remove(0))
+        .unwrap();
+    async_tls::TlsAcceptor::from(Arc::new(config))
+}
+
+#[actix_rt::test]
+async fn test_raw_wss_connection_to_local_server() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // The first client aborts the tls handshake, the second gets a message echoed back
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        assert!(tls_acceptor().accept(socket.compat()).await.is_err());
+        let (socket, _) = listener.accept().await.unwrap();
+        let stream = tls_acceptor().accept(socket.compat()).await.unwrap();
+        let mut socket = util::MockSocket::accept(stream).await;
+        let message = socket.recv_json().await.unwrap();
+        socket.send_json(message).await;
+        socket.drain().await;
+    });
+
+    // The default webpki roots don't trust the certificate
+    let url = format!("wss://localhost:{}/v2/key", port);
+    match RawAlchemyConnection::new().connect(&url).await {
+        Err(AlchemyConnectionError::TlsError(_)) => (),
+        other => panic!("Expected a tls error, got: {:?}", other.map(|_| ())),
+    }
+
+    let mut connection = RawAlchemyConnection::new().with_tls_connector(tls_connector());
+    connection.connect(&url).await.unwrap();
+    assert!(connection.is_connected());
+
+    let (mut writer, mut reader) = connection.split().unwrap();
+    writer
+        .send_text(serde_json::json!({ "id": 1 }).to_string())
+        .await
+        .unwrap();
+    let echoed = reader.receive_text().await.unwrap();
+    assert_eq!(echoed, r#"{"id":1}"#);
+    writer.close().await;
+    server.await.unwrap();
+}
+
+#[actix_rt::test]
+async fn test_raw_ws_connection_to_local_server() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Accept a single websocket handshake, recording the requested resource
+    let server = tokio::spawn(async move {
+        let (socket, _) = listener.accept().await.unwrap();
+        let mut server = soketto::handshake::Server::new(socket.compat());
+        let key = {
+            let request = server.receive_request().await.unwrap();
+            assert_eq!(request.path(), "/v2/key?foo=bar");
+            request.key()
+        };
+        let accept = soketto::handshake::server::Response::Accept {
+            key,
+            protocol: None,
+        };
+        server.send_response(&accept).await.unwrap();
+    });
+
+    let mut connection = RawAlchemyConnection::new();
+    connection
+        .connect(&format!("ws://{}/v2/key?foo=bar", addr))
+        .await
+        .unwrap();
+    assert!(connection.is_connected());
+    server.await.unwrap();
+}
+
+#[actix_rt::test]
+async fn test_raw_connection_rejects_unsupported_schemes() {
+    let mut connection = RawAlchemyConnection::new();
+    match connection
+        .connect("https://eth-mainnet.g.alchemy.com/v2/demo")
+        .await
+    {
+        Err(AlchemyConnectionError::UnsupportedScheme(_)) => (),
+        other => panic!("Expected an unsupported scheme error, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_split_connection_sends_while_receiving() {
+    // Echo every message back to the client
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        while let Some(message) = socket.recv_json().await {
+            socket.send_json(message).await;
+        }
+    })
+    .await;
+
+    let mut connection = RawAlchemyConnection::new();
+    connection.connect(&format!("ws://{}", addr)).await.unwrap();
+    let (mut writer, mut reader) = connection.split().unwrap();
+
+    // The reader is already waiting when the writer sends
+    let receiving = tokio::spawn(async move { reader.receive_text().await });
+    tokio::task::yield_now().await;
+    writer
+        .send_text(serde_json::json!({"id": 1}).to_string())
+        .await
+        .unwrap();
+
+    let text = receiving.await.unwrap().unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+        serde_json::json!({"id": 1})
+    );
+}
+
+#[actix_rt::test]
+async fn test_split_requires_a_connection() {
+    match RawAlchemyConnection::new().split() {
+        Err(AlchemyConnectionError::MissingSender) => (),
+        other => panic!(
+            "Expected a missing sender error, got: {:?}",
+            other.map(|_| ())
+        ),
+    }
+}
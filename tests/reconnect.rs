@@ -0,0 +1,222 @@
+use std::time::Duration;
+
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+
+mod util;
+
+#[test]
+fn test_reconnect_policy_backoff() {
+    let policy = ReconnectPolicy {
+        max_retries: Some(3),
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(500),
+        multiplier: 2.0,
+        jitter: 0.0,
+    };
+    assert_eq!(policy.backoff(0), Duration::from_millis(100));
+    assert_eq!(policy.backoff(2), Duration::from_millis(400));
+    assert_eq!(policy.backoff(3), Duration::from_millis(500));
+    assert_eq!(policy.backoff_with_jitter(1), Duration::from_millis(200));
+    assert!(policy.allows(2));
+    assert!(!policy.allows(3));
+
+    // Jitter spreads the delay around the backoff
+    let jittered = ReconnectPolicy {
+        jitter: 0.5,
+        ..policy
+    };
+    let delay = jittered.backoff_with_jitter(0);
+    assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+
+    assert!(!ReconnectPolicy::disabled().is_enabled());
+}
+
+#[actix_rt::test]
+async fn test_reconnect_and_resubscribe() {
+    // Each connection issues a new subscription id and sends one notification,
+    // the first connection then drops
+    let addr = util::spawn_ws_server(|index, mut socket| async move {
+        let sub_id = util::subscription_hex(index as u128 + 1);
+        let request = socket.recv_json().await.unwrap();
+        assert_eq!(request["method"], "eth_subscribe");
+        socket
+            .respond(&request, serde_json::Value::String(sub_id.clone()))
+            .await;
+        socket
+            .send_json(util::pending_transaction_notification(&sub_id))
+            .await;
+        if index == 0 {
+            socket.close().await;
+        } else {
+            socket.drain().await;
+        }
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None).with_reconnect_policy(
+        ReconnectPolicy {
+            initial_backoff: Duration::from_millis(10),
+            ..Default::default()
+        },
+    );
+    manager.connect().await.unwrap();
+    let sub_id = manager.subscribe(None, None).await.unwrap();
+
+    // The first notification arrives on the original connection
+    let AlchemyInnerResponse::PendingTransactionResult(first) =
+        manager.receive(sub_id).await.unwrap().result;
+    assert_eq!(first.subscription, sub_id);
+
+    // The second arrives after reconnecting, under the original subscription id
+    let AlchemyInnerResponse::PendingTransactionResult(second) =
+        manager.receive(sub_id).await.unwrap().result;
+    assert_eq!(second.subscription, sub_id);
+}
+
+#[actix_rt::test]
+async fn test_reconnect_disabled() {
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        let request = socket.recv_json().await.unwrap();
+        socket
+            .respond(
+                &request,
+                serde_json::Value::String(util::subscription_hex(1)),
+            )
+            .await;
+
+        // Close once the subscription is routed, ie after answering the next request
+        let request = socket.recv_json().await.unwrap();
+        socket
+            .respond(&request, serde_json::Value::String("0x1".into()))
+            .await;
+        socket.close().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None)
+        .with_reconnect_policy(ReconnectPolicy::disabled());
+    manager.connect().await.unwrap();
+    let sub_id = manager.subscribe(None, None).await.unwrap();
+    let _: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    match manager.receive(sub_id).await {
+        Err(AlchemyConnectionError::Closed) => (),
+        other => panic!("Expected a closed connection, got: {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn test_stray_frames_keep_the_connection() {
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        let request = socket.recv_json().await.unwrap();
+        let sub_id = util::subscription_hex(1);
+        socket
+            .respond(&request, serde_json::Value::String(sub_id.clone()))
+            .await;
+
+        // A binary frame is skipped rather than ending the connection
+        socket.sender.send_binary(&[0xff, 0xfe]).await.unwrap();
+        socket.sender.flush().await.unwrap();
+        socket
+            .send_json(util::pending_transaction_notification(&sub_id))
+            .await;
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None)
+        .with_reconnect_policy(ReconnectPolicy::disabled());
+    manager.connect().await.unwrap();
+    let sub_id = manager.subscribe(None, None).await.unwrap();
+    assert_eq!(
+        manager.receive(sub_id).await.unwrap().subscription(),
+        sub_id
+    );
+}
+
+#[actix_rt::test]
+async fn test_resubscribe_times_out_unconfirmed_subscriptions() {
+    // The second connection never confirms the re-issued `newHeads` subscription
+    let addr = util::spawn_ws_server(|index, mut socket| async move {
+        let mut ids = vec![];
+        for n in 1..=2 {
+            let request = socket.recv_json().await.unwrap();
+            assert_eq!(request["method"], "eth_subscribe");
+            ids.push(request["id"].as_u64().unwrap());
+            if index == 1 && request["params"][0] == "newHeads" {
+                continue;
+            }
+            let sub_id = util::subscription_hex(index as u128 * 2 + n);
+            socket
+                .respond(&request, serde_json::Value::String(sub_id.clone()))
+                .await;
+            if index == 1 {
+                socket
+                    .send_json(util::pending_transaction_notification(&sub_id))
+                    .await;
+            }
+        }
+
+        // Every request has an id of its own
+        assert_ne!(ids[0], ids[1]);
+        if index == 0 {
+            socket.close().await;
+        } else {
+            socket.drain().await;
+        }
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None)
+        .with_request_timeout(Duration::from_millis(200))
+        .with_reconnect_policy(ReconnectPolicy {
+            initial_backoff: Duration::from_millis(10),
+            ..Default::default()
+        });
+    manager.connect().await.unwrap();
+    let mut heads = manager.subscribe_new_heads().await.unwrap();
+    let sub_id = manager.subscribe(None, None).await.unwrap();
+
+    // The confirmed subscription is restored, while the unconfirmed one ends
+    let AlchemyInnerResponse::PendingTransactionResult(result) =
+        manager.receive(sub_id).await.unwrap().result;
+    assert_eq!(result.subscription, sub_id);
+    assert!(futures::StreamExt::next(&mut heads).await.is_none());
+}
+
+#[actix_rt::test]
+async fn test_dropping_the_connection_closes_it() {
+    // Every connection reports once the client closed it
+    let (closed, mut closed_rx) = tokio::sync::mpsc::unbounded_channel();
+    let addr = util::spawn_ws_server(move |index, mut socket| {
+        let closed = closed.clone();
+        async move {
+            while let Some(request) = socket.recv_json().await {
+                let result = match request["method"].as_str() {
+                    Some("eth_subscribe") => util::subscription_hex(index as u128 + 1),
+                    _ => "0x1".to_string(),
+                };
+                socket
+                    .respond(&request, serde_json::Value::String(result))
+                    .await;
+            }
+            closed.send(index).unwrap();
+        }
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None);
+    manager.connect().await.unwrap();
+    let client = manager.client().unwrap();
+    manager.subscribe(None, None).await.unwrap();
+
+    // Connecting again shuts the previous connection down, even though a client still holds it
+    manager.connect().await.unwrap();
+    let closed = tokio::time::timeout(Duration::from_secs(5), closed_rx.recv()).await;
+    assert_eq!(closed.unwrap(), Some(0));
+    let _: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+
+    drop(manager);
+    let closed = tokio::time::timeout(Duration::from_secs(5), closed_rx.recv()).await;
+    assert_eq!(closed.unwrap(), Some(1));
+    drop(client);
+}
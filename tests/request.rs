@@ -0,0 +1,50 @@
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+
+mod util;
+
+#[actix_rt::test]
+async fn test_request() {
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        let request = socket.recv_json().await.unwrap();
+        assert_eq!(request["jsonrpc"], "2.0");
+        assert_eq!(request["method"], "eth_blockNumber");
+        assert!(request.get("params").is_none());
+        socket
+            .respond(&request, serde_json::Value::String("0x10".to_string()))
+            .await;
+
+        let request = socket.recv_json().await.unwrap();
+        assert_eq!(request["method"], "eth_getTransactionByHash");
+        assert_eq!(request["params"][0], "0x1234");
+        socket
+            .send_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"],
+                "error": {
+                    "code": -32602,
+                    "message": "invalid argument",
+                },
+            }))
+            .await;
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None);
+    manager.connect().await.unwrap();
+
+    let block_number: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(block_number, U64::from(16));
+
+    // Json-rpc errors are typed
+    match manager
+        .request::<_, Option<Transaction>>("eth_getTransactionByHash", ["0x1234"])
+        .await
+    {
+        Err(AlchemyConnectionError::RpcError(e)) => {
+            assert_eq!(e.code, -32602);
+            assert_eq!(e.message, "invalid argument");
+        }
+        other => panic!("Expected a json-rpc error, got: {:?}", other),
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+use tokio::sync::mpsc;
+
+mod util;
+
+#[test]
+fn test_router_dispatches_by_subscription() {
+    let mut router = SubscriptionRouter::new();
+
+    // Route server subscription 2 to the caller's subscription 1
+    let id = uuid::Uuid::from_u128(1);
+    let (sender, mut receiver) = mpsc::channel(DEFAULT_NOTIFICATION_BUFFER_SIZE);
+    router.insert(util::subscription_hex(2), id, vec![], sender);
+
+    // Notifications are rewritten to the caller's id
+    router
+        .dispatch(&util::pending_transaction_notification(&util::subscription_hex(2)).to_string());
+    let notification = receiver.try_recv().unwrap();
+    assert_eq!(
+        notification["params"]["subscription"],
+        util::subscription_hex(1)
+    );
+
+    // Responses are delivered to the request waiting on their id, in any order
+    let mut seven = router.wait(7);
+    let mut eight = router.wait(8);
+    router.dispatch(r#"{"jsonrpc":"2.0","id":8,"result":true}"#);
+    router.dispatch(r#"{"jsonrpc":"2.0","id":7,"result":false}"#);
+    assert_eq!(seven.try_recv().unwrap()["result"], false);
+    assert_eq!(eight.try_recv().unwrap()["result"], true);
+
+    // Removed routes no longer receive notifications
+    router.remove(id);
+    router
+        .dispatch(&util::pending_transaction_notification(&util::subscription_hex(2)).to_string());
+    assert!(receiver.try_recv().is_err());
+}
+
+#[test]
+fn test_router_buffers_orphaned_notifications() {
+    let mut router = SubscriptionRouter::new();
+
+    // A notification arriving before its route is delivered once the route exists
+    router
+        .dispatch(&util::pending_transaction_notification(&util::subscription_hex(3)).to_string());
+    let (sender, mut receiver) = mpsc::channel(DEFAULT_NOTIFICATION_BUFFER_SIZE);
+    router.insert(
+        util::subscription_hex(3),
+        uuid::Uuid::from_u128(3),
+        vec![],
+        sender,
+    );
+    assert!(receiver.try_recv().is_ok());
+}
+
+#[test]
+fn test_router_drops_notifications_for_full_buffers() {
+    let mut router = SubscriptionRouter::new();
+    let (sender, mut receiver) = mpsc::channel(1);
+    router.insert(
+        util::subscription_hex(4),
+        uuid::Uuid::from_u128(4),
+        vec![],
+        sender,
+    );
+
+    // The second notification overflows the buffer, but the subscription stays routed
+    for _ in 0..2 {
+        router.dispatch(
+            &util::pending_transaction_notification(&util::subscription_hex(4)).to_string(),
+        );
+    }
+    assert!(receiver.try_recv().is_ok());
+    assert!(receiver.try_recv().is_err());
+    assert_eq!(router.len(), 1);
+
+    router
+        .dispatch(&util::pending_transaction_notification(&util::subscription_hex(4)).to_string());
+    assert!(receiver.try_recv().is_ok());
+}
+
+#[actix_rt::test]
+async fn test_receive_only_returns_the_subscription_messages() {
+    // Confirm two subscriptions, then interleave their notifications
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        for n in 1..=2 {
+            let request = socket.recv_json().await.unwrap();
+            socket
+                .respond(
+                    &request,
+                    serde_json::Value::String(util::subscription_hex(n)),
+                )
+                .await;
+        }
+        for n in [2, 1, 2, 1] {
+            socket
+                .send_json(util::pending_transaction_notification(
+                    &util::subscription_hex(n),
+                ))
+                .await;
+        }
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None);
+    manager.connect().await.unwrap();
+    let first = manager.subscribe(None, None).await.unwrap();
+    let second = manager.subscribe(None, None).await.unwrap();
+    assert_ne!(first, second);
+
+    for _ in 0..2 {
+        assert_eq!(
+            manager.receive(second).await.unwrap().subscription(),
+            second
+        );
+    }
+    for _ in 0..2 {
+        assert_eq!(manager.receive(first).await.unwrap().subscription(), first);
+    }
+
+    // Unknown subscriptions are rejected
+    match manager.receive(uuid::Uuid::from_u128(42)).await {
+        Err(AlchemyConnectionError::UnknownSubscription(_)) => (),
+        other => panic!("Expected an unknown subscription error, got: {:?}", other),
+    }
+}
+
+#[test]
+fn test_request_ids_are_monotonic() {
+    let ids = RequestIdAllocator::new();
+    let first = ids.next_id();
+    assert_eq!(first, FIRST_REQUEST_ID);
+    assert_eq!(ids.next_id(), first + 1);
+}
+
+#[actix_rt::test]
+async fn test_responses_are_correlated_by_id() {
+    // Precede the response with a notification and a response to an unknown request
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        let request = socket.recv_json().await.unwrap();
+        socket
+            .send_json(util::pending_transaction_notification(
+                &util::subscription_hex(9),
+            ))
+            .await;
+        socket
+            .send_json(serde_json::json!({
+                "jsonrpc": "2.0",
+                "id": request["id"].as_u64().unwrap() + 100,
+                "result": util::subscription_hex(8),
+            }))
+            .await;
+        socket
+            .respond(
+                &request,
+                serde_json::Value::String(util::subscription_hex(1)),
+            )
+            .await;
+
+        // Never answer the second request
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None)
+        .with_request_timeout(Duration::from_millis(100));
+    manager.connect().await.unwrap();
+    let sub_id = manager.subscribe(None, None).await.unwrap();
+    assert_eq!(sub_id, uuid::Uuid::from_u128(1));
+
+    match manager.subscribe(None, None).await {
+        Err(AlchemyConnectionError::RequestTimeout(_)) => (),
+        other => panic!("Expected a request timeout, got: {:?}", other),
+    }
+}
@@ -0,0 +1,184 @@
+use std::sync::{Arc, Mutex};
+
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+use futures::StreamExt;
+use tokio::sync::mpsc;
+
+mod util;
+
+#[actix_rt::test]
+async fn test_subscription_streams() {
+    // Confirm two subscriptions, then interleave their notifications
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        for n in 1..=2 {
+            let request = socket.recv_json().await.unwrap();
+            socket
+                .respond(
+                    &request,
+                    serde_json::Value::String(util::subscription_hex(n)),
+                )
+                .await;
+        }
+        for n in [2, 1, 2, 1] {
+            socket
+                .send_json(util::pending_transaction_notification(
+                    &util::subscription_hex(n),
+                ))
+                .await;
+        }
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None);
+    manager.connect().await.unwrap();
+    let first = manager
+        .subscribe_pending_transactions(None, None)
+        .await
+        .unwrap();
+    let second = manager
+        .subscribe_pending_transactions(None, None)
+        .await
+        .unwrap();
+    assert_ne!(first.id(), second.id());
+
+    // Each stream only yields its own notifications, even from another task
+    let second = tokio::spawn(async move { second.take(2).collect::<Vec<_>>().await });
+    let first = first.take(2).collect::<Vec<_>>().await;
+    let second = second.await.unwrap();
+    assert_eq!(first.len(), 2);
+    assert_eq!(second.len(), 2);
+    assert!(first.into_iter().chain(second).all(|tx| tx.is_ok()));
+}
+
+#[actix_rt::test]
+async fn test_unsubscribe() {
+    let addr = util::spawn_ws_server(|_, mut socket| async move {
+        let request = socket.recv_json().await.unwrap();
+        socket
+            .respond(
+                &request,
+                serde_json::Value::String(util::subscription_hex(1)),
+            )
+            .await;
+
+        // The connection's subscription id is unsubscribed
+        let request = socket.recv_json().await.unwrap();
+        assert_eq!(request["method"], "eth_unsubscribe");
+        assert_eq!(request["params"][0], util::subscription_hex(1));
+        socket
+            .respond(&request, serde_json::Value::Bool(true))
+            .await;
+        socket.drain().await;
+    })
+    .await;
+
+    let mut manager = AlchemyManager::new(&format!("ws://{}", addr), None);
+    manager.connect().await.unwrap();
+    let sub_id = manager.subscribe(None, None).await.unwrap();
+    assert!(manager.unsubscribe(sub_id).await.unwrap());
+
+    // The subscription is forgotten
+    match manager.receive(sub_id).await {
+        Err(AlchemyConnectionError::UnknownSubscription(_)) => (),
+        other => panic!("Expected an unknown subscription error, got: {:?}", other),
+    }
+    match manager.unsubscribe(sub_id).await {
+        Err(AlchemyConnectionError::UnknownSubscription(_)) => (),
+        other => panic!("Expected an unknown subscription error, got: {:?}", other),
+    }
+}
+
+#[test]
+fn test_dropping_the_stream_unsubscribes() {
+    let router = Arc::new(Mutex::new(SubscriptionRouter::new()));
+    let (outbound, mut outbound_rx) = mpsc::unbounded_channel();
+
+    let id = uuid::Uuid::from_u128(1);
+    let (sender, receiver) = mpsc::channel(DEFAULT_NOTIFICATION_BUFFER_SIZE);
+    router
+        .lock()
+        .unwrap()
+        .insert(util::subscription_hex(2), id, vec![], sender);
+    let registry = Arc::new(RouteRegistry::new());
+    let binding = RouteBinding {
+        router: router.clone(),
+        outbound,
+    };
+    assert_eq!(
+        registry.bind(&util::subscription_hex(1), binding.clone()),
+        id
+    );
+
+    // Ids another connection handed out already are replaced with unique ones
+    assert_ne!(registry.bind(&util::subscription_hex(1), binding), id);
+    let stream = SubscriptionStream::<Transaction>::new(id, receiver, registry);
+    assert_eq!(router.lock().unwrap().len(), 1);
+
+    // The route is released and the connection's subscription id unsubscribed
+    drop(stream);
+    assert!(router.lock().unwrap().is_empty());
+    let message: serde_json::Value =
+        serde_json::from_str(&outbound_rx.try_recv().unwrap()).unwrap();
+    assert_eq!(message["method"], "eth_unsubscribe");
+    assert_eq!(message["params"][0], util::subscription_hex(2));
+}
+
+/// Spawns a server confirming every subscription with a notification, and reporting the connection index and
+/// server id of every `eth_unsubscribe`
+///
+/// Every connection numbers its subscription ids after its own index.
+async fn spawn_unsubscribe_reporting_server() -> (
+    std::net::SocketAddr,
+    mpsc::UnboundedReceiver<(usize, serde_json::Value)>,
+) {
+    let (unsubscribed, unsubscribed_rx) = mpsc::unbounded_channel();
+    let addr = util::spawn_ws_server(move |index, mut socket| {
+        let unsubscribed = unsubscribed.clone();
+        async move {
+            while let Some(request) = socket.recv_json().await {
+                if request["method"] == "eth_unsubscribe" {
+                    let _ = unsubscribed.send((index, request["params"][0].clone()));
+                    socket.respond(&request, true.into()).await;
+                    continue;
+                }
+                let sub_id = util::subscription_hex(index as u128 + 1);
+                socket
+                    .respond(&request, serde_json::Value::String(sub_id.clone()))
+                    .await;
+                socket
+                    .send_json(util::pending_transaction_notification(&sub_id))
+                    .await;
+            }
+        }
+    })
+    .await;
+    (addr, unsubscribed_rx)
+}
+
+#[actix_rt::test]
+async fn test_dropping_the_stream_after_connecting_again_unsubscribes() {
+    for connector_type in [AlchemyConnectorType::Raw, AlchemyConnectorType::Pool] {
+        let (addr, mut unsubscribed) = spawn_unsubscribe_reporting_server().await;
+        let mut manager =
+            AlchemyManager::new(&format!("ws://{}", addr), Some(connector_type)).with_pool_size(1);
+        manager.connect().await.unwrap();
+        let mut stream = manager
+            .subscribe_stream::<serde_json::Value>(vec!["alchemy_pendingTransactions".into()])
+            .await
+            .unwrap();
+        stream.next().await.unwrap().unwrap();
+
+        // The subscription is re-issued on the new connection, and unsubscribed there once dropped
+        manager.connect().await.unwrap();
+        stream.next().await.unwrap().unwrap();
+        drop(stream);
+        let (index, server_id) =
+            tokio::time::timeout(std::time::Duration::from_secs(5), unsubscribed.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(server_id, util::subscription_hex(2));
+    }
+}
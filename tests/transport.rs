@@ -0,0 +1,118 @@
+use std::{sync::Arc, time::Duration};
+
+use alchemy_rs::{connectors::prelude::*, prelude::*};
+use futures::StreamExt;
+use tokio_util::compat::TokioAsyncReadCompatExt;
+
+mod util;
+
+#[actix_rt::test]
+async fn test_memory_transport_drives_the_manager() {
+    let (transport, mut listener) = MemoryTransport::new(64 * 1024);
+
+    // The first connection confirms the subscription and drops,
+    // the second one answers the resubscription and a request
+    let server = tokio::spawn(async move {
+        let stream = listener.accept().await.unwrap();
+        let mut socket = util::MockSocket::accept(stream.compat()).await;
+        let request = socket.recv_json().await.unwrap();
+        assert_eq!(request["method"], "eth_subscribe");
+        let sub_id = util::subscription_hex(1);
+        socket
+            .respond(&request, serde_json::Value::String(sub_id.clone()))
+            .await;
+        socket
+            .send_json(util::pending_transaction_notification(&sub_id))
+            .await;
+        socket.close().await;
+
+        let stream = listener.accept().await.unwrap();
+        let mut socket = util::MockSocket::accept(stream.compat()).await;
+        let request = socket.recv_json().await.unwrap();
+        assert_eq!(request["method"], "eth_subscribe");
+        let sub_id = util::subscription_hex(2);
+        socket
+            .respond(&request, serde_json::Value::String(sub_id.clone()))
+            .await;
+        socket
+            .send_json(util::pending_transaction_notification(&sub_id))
+            .await;
+        let request = socket.recv_json().await.unwrap();
+        assert_eq!(request["method"], "eth_blockNumber");
+        socket
+            .respond(&request, serde_json::Value::String("0x10".to_string()))
+            .await;
+        socket.drain().await;
+    });
+
+    let mut manager = AlchemyManager::builder()
+        .with_url("ws://memory/v2/key")
+        .with_transport(Arc::new(transport))
+        .with_reconnect_policy(ReconnectPolicy {
+            initial_backoff: Duration::from_millis(10),
+            jitter: 0.0,
+            ..ReconnectPolicy::default()
+        })
+        .build()
+        .unwrap();
+    manager.connect().await.unwrap();
+
+    // Notifications keep flowing across the reconnect
+    let mut stream = manager
+        .subscribe_pending_transactions(None, None)
+        .await
+        .unwrap();
+    stream.next().await.unwrap().unwrap();
+    stream.next().await.unwrap().unwrap();
+
+    let block_number: U64 = manager.request("eth_blockNumber", ()).await.unwrap();
+    assert_eq!(block_number, U64::from(16));
+
+    drop(stream);
+    drop(manager);
+    server.await.unwrap();
+}
+
+#[actix_rt::test]
+async fn test_memory_transport_without_listener() {
+    let (transport, listener) = MemoryTransport::new(1024);
+    drop(listener);
+
+    let mut connection = RawAlchemyConnection::new().with_transport(Arc::new(transport));
+    match connection.connect("ws://memory/").await {
+        Err(AlchemyConnectionError::RawStreamError(_)) => (),
+        other => panic!("Expected a stream error, got: {:?}", other),
+    }
+}
+
+#[cfg(unix)]
+#[actix_rt::test]
+async fn test_unix_transport() {
+    let path = std::env::temp_dir().join(format!("alchemy-rs-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let listener = tokio::net::UnixListener::bind(&path).unwrap();
+
+    // Echo the first message back to the client
+    let server = tokio::spawn(async move {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut socket = util::MockSocket::accept(stream.compat()).await;
+        let message = socket.recv_json().await.unwrap();
+        socket.send_json(message).await;
+    });
+
+    let mut connection =
+        RawAlchemyConnection::new().with_transport(Arc::new(UnixTransport::new(&path)));
+    connection.connect("ws://localhost/").await.unwrap();
+    connection
+        .send_text(serde_json::json!({"id": 1}).to_string())
+        .await
+        .unwrap();
+    let text = connection.receive_text().await.unwrap();
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&text).unwrap(),
+        serde_json::json!({"id": 1})
+    );
+
+    server.await.unwrap();
+    std::fs::remove_file(&path).unwrap();
+}
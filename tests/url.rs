@@ -0,0 +1,93 @@
+use alchemy_rs::connectors::prelude::*;
+
+#[test]
+fn test_parse_alchemy_wss_url() {
+    let url =
+        AlchemyUrl::parse("wss://eth-mainnet.g.alchemy.com/v2/MVNYMOb_58bAMzhXX2pS25NDiZ3Q9HeC")
+            .unwrap();
+    assert_eq!(url.scheme, WsScheme::Wss);
+    assert_eq!(url.host, "eth-mainnet.g.alchemy.com");
+    assert_eq!(url.port, 443);
+    assert_eq!(url.socket_addr(), ("eth-mainnet.g.alchemy.com", 443));
+    assert_eq!(url.host_header(), "eth-mainnet.g.alchemy.com");
+    assert_eq!(url.resource(), "/v2/MVNYMOb_58bAMzhXX2pS25NDiZ3Q9HeC");
+    assert!(url.is_secure());
+}
+
+#[test]
+fn test_parse_local_ws_url() {
+    let url = AlchemyUrl::parse("ws://127.0.0.1:8546/rpc?token=abc").unwrap();
+    assert_eq!(url.scheme, WsScheme::Ws);
+    assert_eq!(url.socket_addr(), ("127.0.0.1", 8546));
+    assert_eq!(url.host_header(), "127.0.0.1:8546");
+    assert_eq!(url.resource(), "/rpc?token=abc");
+    assert!(!url.is_secure());
+
+    // An empty path is sent as the root resource
+    let url = AlchemyUrl::parse("ws://localhost").unwrap();
+    assert_eq!(url.port, 80);
+    assert_eq!(url.resource(), "/");
+    assert_eq!(url.to_string(), "ws://localhost/");
+}
+
+#[test]
+fn test_parse_invalid_urls() {
+    match AlchemyUrl::parse("https://eth-mainnet.g.alchemy.com/v2/demo") {
+        Err(AlchemyConnectionError::UnsupportedScheme(s)) => assert_eq!(s, "https"),
+        other => panic!("Expected an unsupported scheme error, got: {:?}", other),
+    }
+    match AlchemyUrl::parse("not a url") {
+        Err(AlchemyConnectionError::InvalidUrl(_)) => (),
+        other => panic!("Expected an invalid url error, got: {:?}", other),
+    }
+}
+
+#[test]
+fn test_resolve_redirect_locations() {
+    let url = AlchemyUrl::parse("ws://127.0.0.1:8546/v2/key").unwrap();
+
+    // Relative locations keep the scheme, host and port
+    let redirected = url.redirect("/v2/other", false).unwrap();
+    assert_eq!(redirected.to_string(), "ws://127.0.0.1:8546/v2/other");
+
+    // Http locations are mapped to their websocket scheme, once redirects to other hosts are allowed
+    match url.redirect("https://eth-mainnet.g.alchemy.com/v2/key", false) {
+        Err(AlchemyConnectionError::CrossHostRedirect(_)) => (),
+        other => panic!("Expected a cross-host redirect error, got: {:?}", other),
+    }
+    let redirected = url
+        .redirect("https://eth-mainnet.g.alchemy.com/v2/key", true)
+        .unwrap();
+    assert_eq!(redirected.scheme, WsScheme::Wss);
+    assert_eq!(redirected.port, 443);
+
+    // Secure urls are never downgraded, even on the same host
+    let url = AlchemyUrl::parse("wss://eth-mainnet.g.alchemy.com/v2/key").unwrap();
+    for location in [
+        "ws://eth-mainnet.g.alchemy.com/v2/key",
+        "http://eth-mainnet.g.alchemy.com/v2/key",
+    ] {
+        match url.redirect(location, true) {
+            Err(AlchemyConnectionError::InsecureRedirect(_)) => (),
+            other => panic!("Expected an insecure redirect error, got: {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_redacted_url_leaves_out_the_api_key() {
+    let url =
+        AlchemyUrl::parse("wss://eth-mainnet.g.alchemy.com/v2/MVNYMOb_58bAMzhXX2pS25NDiZ3Q9HeC")
+            .unwrap();
+    assert_eq!(
+        url.redacted().to_string(),
+        "wss://eth-mainnet.g.alchemy.com"
+    );
+
+    // Non-default ports are kept, queries are left out along with the path
+    assert_eq!(
+        redact_url("ws://127.0.0.1:8546/rpc?token=abc"),
+        "ws://127.0.0.1:8546"
+    );
+    assert_eq!(redact_url("not a url"), "<invalid url>");
+}
@@ -1,5 +1,222 @@
+#![allow(dead_code)]
+
+use std::{future::Future, net::SocketAddr, sync::Arc};
+
+use futures::io::{AsyncRead, AsyncWrite};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
+
 pub fn assert_strings_roughly_equal(a: impl Into<String>, b: impl Into<String>) {
     let undressed_a = (a.into() as String).as_str().replace(['\n', ' ', '\t'], "");
     let undressed_b = (b.into() as String).as_str().replace(['\n', ' ', '\t'], "");
     assert_eq!(undressed_a, undressed_b);
 }
+
+/// A server-side websocket connection accepted by the [spawn_ws_server] stand-in
+pub struct MockSocket<S = Compat<TcpStream>> {
+    /// The websocket sender
+    pub sender: soketto::Sender<S>,
+    /// The websocket receiver
+    pub receiver: soketto::Receiver<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> MockSocket<S> {
+    /// Accept the websocket handshake on the stream
+    pub async fn accept(stream: S) -> Self {
+        let mut server = soketto::handshake::Server::new(stream);
+        let key = server.receive_request().await.unwrap().key();
+        let accept = soketto::handshake::server::Response::Accept {
+            key,
+            protocol: None,
+        };
+        server.send_response(&accept).await.unwrap();
+        let (sender, receiver) = server.into_builder().finish();
+        Self { sender, receiver }
+    }
+
+    /// Receive the next json message, returning `None` once the connection closes
+    pub async fn recv_json(&mut self) -> Option<serde_json::Value> {
+        let mut data = vec![];
+        match self.receiver.receive_data(&mut data).await {
+            Ok(_) => serde_json::from_slice(&data).ok(),
+            Err(_) => None,
+        }
+    }
+
+    /// Send a json message
+    pub async fn send_json(&mut self, value: serde_json::Value) {
+        self.sender.send_text(value.to_string()).await.unwrap();
+        self.sender.flush().await.unwrap();
+    }
+
+    /// Answer the request with the given json-rpc result
+    pub async fn respond(&mut self, request: &serde_json::Value, result: serde_json::Value) {
+        self.send_json(serde_json::json!({
+            "jsonrpc": "2.0",
+            "id": request["id"],
+            "result": result,
+        }))
+        .await;
+    }
+
+    /// Wait until the client closes the connection
+    pub async fn drain(&mut self) {
+        while self.recv_json().await.is_some() {}
+    }
+
+    /// Close the connection
+    pub async fn close(mut self) {
+        let _ = self.sender.close().await;
+    }
+}
+
+/// Spawns a stand-in websocket server, handing every accepted connection and its index to the handler
+pub async fn spawn_ws_server<F, Fut>(handler: F) -> SocketAddr
+where
+    F: Fn(usize, MockSocket) -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut index = 0;
+        while let Ok((socket, _)) = listener.accept().await {
+            let socket = MockSocket::accept(socket.compat()).await;
+            tokio::spawn(handler(index, socket));
+            index += 1;
+        }
+    });
+    addr
+}
+
+/// A request received by the [spawn_http_server] stand-in
+pub struct HttpRequest {
+    /// The index of the tcp connection the request arrived on
+    pub connection: usize,
+    /// The request headers, with lowercase names
+    pub headers: Vec<(String, String)>,
+    /// The json body
+    pub body: serde_json::Value,
+}
+
+impl HttpRequest {
+    /// The value of the header
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Spawns a stand-in http json-rpc server, answering every request with the handler's status and json body
+///
+/// Connections are kept alive, so several requests can arrive on one connection, and rate limited (429) responses
+/// ask to retry after two seconds.
+pub async fn spawn_http_server<F>(handler: F) -> SocketAddr
+where
+    F: Fn(HttpRequest) -> (u16, serde_json::Value) + Send + Sync + 'static,
+{
+    serve_http(handler, false).await
+}
+
+/// Spawns a stand-in http json-rpc server like [spawn_http_server], gzip-encoding every response body
+pub async fn spawn_gzip_http_server<F>(handler: F) -> SocketAddr
+where
+    F: Fn(HttpRequest) -> (u16, serde_json::Value) + Send + Sync + 'static,
+{
+    serve_http(handler, true).await
+}
+
+async fn serve_http<F>(handler: F, gzip: bool) -> SocketAddr
+where
+    F: Fn(HttpRequest) -> (u16, serde_json::Value) + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = Arc::new(handler);
+    tokio::spawn(async move {
+        let mut index = 0;
+        while let Ok((socket, _)) = listener.accept().await {
+            let handler = Arc::clone(&handler);
+            let connection = index;
+            index += 1;
+            tokio::spawn(async move {
+                let mut socket = tokio::io::BufReader::new(socket);
+                loop {
+                    // The request line, ending the connection once the client closes it
+                    let mut line = String::new();
+                    if socket.read_line(&mut line).await.unwrap_or(0) == 0 {
+                        return;
+                    }
+
+                    let mut headers = vec![];
+                    loop {
+                        line.clear();
+                        socket.read_line(&mut line).await.unwrap();
+                        match line.trim_end().split_once(':') {
+                            Some((name, value)) => headers
+                                .push((name.trim().to_ascii_lowercase(), value.trim().to_string())),
+                            None => break,
+                        }
+                    }
+                    let length = headers
+                        .iter()
+                        .find(|(name, _)| name == "content-length")
+                        .map(|(_, value)| value.parse::<usize>().unwrap())
+                        .unwrap_or(0);
+                    let mut body = vec![0; length];
+                    socket.read_exact(&mut body).await.unwrap();
+
+                    let (status, response) = handler(HttpRequest {
+                        connection,
+                        headers,
+                        body: serde_json::from_slice(&body).unwrap(),
+                    });
+                    let mut response = response.to_string().into_bytes();
+                    let mut extra = String::new();
+                    if status == 429 {
+                        extra.push_str("retry-after: 2\r\n");
+                    }
+                    if gzip {
+                        let mut encoder =
+                            flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+                        std::io::Write::write_all(&mut encoder, &response).unwrap();
+                        response = encoder.finish().unwrap();
+                        extra.push_str("content-encoding: gzip\r\n");
+                    }
+                    let head = format!(
+                        "HTTP/1.1 {} Stand-in\r\ncontent-type: application/json\r\n{}content-length: {}\r\n\r\n",
+                        status,
+                        extra,
+                        response.len()
+                    );
+                    let socket = socket.get_mut();
+                    socket.write_all(head.as_bytes()).await.unwrap();
+                    socket.write_all(&response).await.unwrap();
+                }
+            });
+        }
+    });
+    addr
+}
+
+/// Formats a subscription id as the server would
+pub fn subscription_hex(n: u128) -> String {
+    format!("0x{:032x}", n)
+}
+
+/// An `alchemy_pendingTransactions` notification for the given subscription id
+pub fn pending_transaction_notification(subscription: &str) -> serde_json::Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "eth_subscription",
+        "params": {
+            "subscription": subscription,
+            "result": ethers::types::Transaction::default(),
+        },
+    })
+}